@@ -0,0 +1,139 @@
+use crate::registry::SYSTEM_CALLER;
+use clap::{Parser, ValueEnum};
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+#[derive(Parser, Debug, Clone)]
+#[command(version, about = "trtcp event broker")]
+pub struct Config {
+    /// TCP port where the clients connect
+    #[arg(default_value_t = 1237)]
+    pub port: u16,
+
+    /// Don't listen on TCP, only on the Unix socket
+    #[arg(long, requires = "unix")]
+    pub no_tcp: bool,
+
+    /// Path of a Unix domain socket where the clients can connect too
+    #[arg(long)]
+    pub unix: Option<PathBuf>,
+
+    /// Permissions of the Unix socket file, in octal
+    #[arg(long, value_parser = parse_mode, default_value = "660")]
+    pub unix_mode: u32,
+
+    /// Address of the WebSocket listener, every binary message carries one trtcp frame
+    #[arg(long)]
+    pub ws: Option<SocketAddr>,
+
+    /// Address of the newline delimited JSON listener, meant for debugging and scripting
+    #[arg(long)]
+    pub json: Option<SocketAddr>,
+
+    /// Address of the HTTP admin API, to inspect the broker and invoke events
+    #[arg(long)]
+    pub http: Option<SocketAddr>,
+
+    /// Token of the HTTP admin API, as CALLER=TOKEN. Requests with `Authorization: Bearer TOKEN`
+    /// act as CALLER and the ones without a known token are rejected. Can be repeated.
+    /// Without tokens the API only binds to a loopback address and acts as `http`
+    #[arg(long = "http-token", value_name = "CALLER=TOKEN", value_parser = parse_http_token)]
+    pub http_tokens: Vec<HttpToken>,
+
+    /// Address of a listener that only serves the prometheus metrics, on /metrics.
+    /// The HTTP admin API serves them too
+    #[arg(long)]
+    pub metrics: Option<SocketAddr>,
+
+    /// SQLite database where the events and the durable subscriptions are persisted.
+    /// Without it they only live in memory
+    #[arg(long)]
+    pub sqlite: Option<PathBuf>,
+
+    /// Bytes of callbacks kept for each offline client with durable subscriptions,
+    /// the oldest ones are dropped first
+    #[arg(long, default_value_t = 1024 * 1024)]
+    pub offline_max_bytes: usize,
+
+    /// Seconds a callback waits for an offline client before it's dropped
+    #[arg(long, default_value_t = 3600)]
+    pub offline_max_age: u64,
+
+    /// Milliseconds a listener in ack mode has to acknowledge a callback before it's sent again
+    #[arg(long, default_value_t = 30_000)]
+    pub ack_timeout_ms: u64,
+
+    /// Times a callback is sent to a listener in ack mode before it goes to the dead-letter event
+    #[arg(long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+    pub max_attempts: u32,
+
+    /// Highest retention an event can be created with, in invokes
+    #[arg(long, default_value_t = 10_000)]
+    pub max_retention: usize,
+
+    /// Dead letters kept for history requests on the dead-letter event, up to `max_retention`
+    #[arg(long, default_value_t = 1_000)]
+    pub dead_letter_retention: usize,
+
+    /// Milliseconds a request waits for the reply of its responder, unless it sets a timeout
+    #[arg(long, default_value_t = 5_000)]
+    pub request_timeout_ms: u64,
+
+    /// Longest a request or a gather invoke can ask to wait for replies, in milliseconds.
+    /// The caller doesn't get other frames while it waits
+    #[arg(long, default_value_t = 30_000)]
+    pub max_request_timeout_ms: u64,
+
+    /// Milliseconds a connection can go without requests before it's dropped as timed out
+    #[arg(long)]
+    pub idle_timeout_ms: Option<u64>,
+
+    /// Caller allowed to manage every event, not only the ones it created. Can be repeated
+    #[arg(long = "admin", value_name = "NAME")]
+    pub admins: Vec<String>,
+
+    /// PEM certificate chain of the broker. Enables TLS on the TCP and WebSocket listeners
+    #[arg(long, requires = "tls_key")]
+    pub tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the broker certificate
+    #[arg(long, requires = "tls_cert")]
+    pub tls_key: Option<PathBuf>,
+
+    /// PEM CA bundle used to verify client certificates. Enables mutual TLS
+    #[arg(long, requires = "tls_cert")]
+    pub tls_client_ca: Option<PathBuf>,
+
+    /// How the CN of the client certificate relates to the caller name
+    #[arg(long, value_enum, default_value_t = CnMode::Check)]
+    pub tls_cn: CnMode,
+}
+
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq)]
+pub enum CnMode {
+    /// The CN becomes the caller name, whatever the client sent on connect
+    Set,
+    /// The caller name sent on connect must be the CN
+    Check,
+}
+
+#[derive(Debug, Clone)]
+pub struct HttpToken {
+    pub caller: String,
+    pub token: String,
+}
+
+fn parse_http_token(value: &str) -> Result<HttpToken, String> {
+    match value.split_once('=') {
+        Some((SYSTEM_CALLER, _)) => Err(format!("{} is reserved for the broker", SYSTEM_CALLER)),
+        Some((caller, token)) if !caller.is_empty() && !token.is_empty() => Ok(HttpToken {
+            caller: caller.to_string(),
+            token: token.to_string(),
+        }),
+        _ => Err(format!("{} is not CALLER=TOKEN", value)),
+    }
+}
+
+fn parse_mode(mode: &str) -> Result<u32, String> {
+    u32::from_str_radix(mode, 8).map_err(|_| format!("{} is not an octal file mode", mode))
+}
@@ -0,0 +1,344 @@
+use crate::registry::{self, Listener};
+use crate::{metrics, store, CLIENT_WRITERS};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use tracing::{error, info, warn};
+use trtcp::{Action, ActionType, Head, Request};
+
+/// Event where the callbacks that were never acknowledged end up
+pub const DEAD_LETTER_EVENT: &str = "camelot:deadLetter";
+
+const DELIVERY_ID_HEADER: &str = "delivery-id";
+const REDELIVERED_HEADER: &str = "redelivered";
+
+/// Callback of an ack subscription that hasn't been acknowledged yet
+struct PendingAck {
+    listener: String,
+    durable: bool,
+    callback: Callback,
+    attempts: u32,
+    sent_at: Instant,
+}
+
+static PENDING_ACKS: LazyLock<Mutex<HashMap<u64, PendingAck>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_DELIVERY_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Owned parts of a callback request, so it can be built again for every listener that
+/// needs its own headers
+#[derive(Clone, Debug)]
+pub struct Callback {
+    pub caller: String,
+    pub module: String,
+    pub id: String,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl Callback {
+    pub fn new(caller: &str, module: &str, id: &str, body: &[u8]) -> Self {
+        Self {
+            caller: caller.to_string(),
+            module: module.to_string(),
+            id: id.to_string(),
+            headers: Vec::new(),
+            body: body.to_vec(),
+        }
+    }
+
+    pub fn event_name(&self) -> String {
+        format!("{}:{}", self.module, self.id)
+    }
+
+    /// Serializes the callback with the extra headers after its own ones
+    pub fn frame(&self, extra_headers: &[(&str, &str)]) -> Vec<u8> {
+        let head = self
+            .headers
+            .iter()
+            .map(|(key, value)| (key.as_str(), value.as_str()))
+            .chain(extra_headers.iter().copied())
+            .fold(Head::new_with_version(&self.caller), |head, (key, value)| {
+                head.with_header(key, value)
+            });
+
+        Request::new(
+            head,
+            Action::new(ActionType::Callback, &self.module, &self.id),
+            self.body.as_slice(),
+        )
+        .into()
+    }
+}
+
+#[derive(Serialize)]
+#[serde(rename_all = "camelCase")]
+struct DeadLetter<'a> {
+    delivery_id: u64,
+    event: String,
+    listener: &'a str,
+    caller: &'a str,
+    attempts: u32,
+    body: std::borrow::Cow<'a, str>,
+}
+
+/// Outcome of a fan-out, sent back to the invokers that ask for it
+#[derive(Debug, Default, Serialize)]
+pub struct DeliveryReport {
+    /// Listeners the callback was meant for
+    pub matched: usize,
+    pub delivered: Vec<String>,
+    /// Offline durable listeners that will get it when they connect again
+    pub queued: Vec<String>,
+    pub failed: Vec<FailedDelivery>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct FailedDelivery {
+    pub listener: String,
+    pub error: String,
+}
+
+impl DeliveryReport {
+    fn fail(&mut self, listener: &str, error: impl ToString) {
+        self.failed.push(FailedDelivery {
+            listener: listener.to_string(),
+            error: error.to_string(),
+        });
+    }
+}
+
+/// Writes the callback to every listener. Offline durable listeners get it queued and
+/// the ones in ack mode get it tracked until they acknowledge it
+pub async fn fan_out(listeners: &[Listener], callback: &Callback, received_at: Instant) -> DeliveryReport {
+    let call_bytes = callback.frame(&[]);
+    metrics::FAN_OUT.observe(listeners.len() as f64);
+
+    let mut report = DeliveryReport {
+        matched: listeners.len(),
+        ..Default::default()
+    };
+
+    let guard = CLIENT_WRITERS.read().await;
+    for listener in listeners.iter() {
+        let ack_frame;
+        let frame = if listener.ack {
+            let delivery_id = track(listener, callback).await;
+            ack_frame = callback.frame(&[(DELIVERY_ID_HEADER, &delivery_id.to_string())]);
+            &ack_frame
+        } else {
+            &call_bytes
+        };
+
+        let mut writer = if let Some(c) = guard.get(&listener.name) {
+            c.lock().await
+        } else if listener.durable {
+            queue_callback(&listener.name, frame, &mut report);
+            continue
+        } else {
+            // TODO: Remove the client name from the listeners
+            warn!("Client {} not found but is registered as a listener", listener.name);
+            metrics::CALLBACKS_FAILED.with_label_values(&["disconnected"]).inc();
+            report.fail(&listener.name, "Not connected");
+            continue
+        };
+
+        if let Err(e) = writer.write_slice(frame).await {
+            warn!("Failed to send callback_request to client {}: {}", listener.name, e);
+            metrics::CALLBACKS_FAILED.with_label_values(&["write_error"]).inc();
+            report.fail(&listener.name, &e);
+            if listener.durable {
+                queue_callback(&listener.name, frame, &mut report);
+            }
+            continue;
+        }
+
+        metrics::CALLBACKS_SENT.inc();
+        metrics::BYTES_OUT.inc_by(frame.len() as u64);
+        metrics::DELIVERY_LATENCY.observe(received_at.elapsed().as_secs_f64());
+        report.delivered.push(listener.name.clone());
+    }
+
+    report
+}
+
+/// Writes a frame to a connected client, returns false when it couldn't
+pub async fn send(client: &str, frame: &[u8]) -> bool {
+    let guard = CLIENT_WRITERS.read().await;
+    let Some(writer) = guard.get(client) else {
+        metrics::CALLBACKS_FAILED.with_label_values(&["disconnected"]).inc();
+        return false;
+    };
+
+    if let Err(e) = writer.lock().await.write_slice(frame).await {
+        warn!("Failed to send a frame to client {}: {}", client, e);
+        metrics::CALLBACKS_FAILED.with_label_values(&["write_error"]).inc();
+        return false;
+    }
+
+    metrics::CALLBACKS_SENT.inc();
+    metrics::BYTES_OUT.inc_by(frame.len() as u64);
+    true
+}
+
+/// Keeps the callback of a durable listener until it connects again
+fn queue_callback(listener: &str, call_bytes: &[u8], report: &mut DeliveryReport) {
+    match store::enqueue_offline(listener, call_bytes) {
+        Ok(_) => {
+            metrics::CALLBACKS_QUEUED.inc();
+            report.queued.push(listener.to_string());
+        }
+        Err(e) => {
+            error!("Could not queue the callback for {}: {}", listener, e);
+            metrics::CALLBACKS_FAILED.with_label_values(&["store_error"]).inc();
+            report.fail(listener, &e);
+        }
+    }
+}
+
+async fn track(listener: &Listener, callback: &Callback) -> u64 {
+    let delivery_id = NEXT_DELIVERY_ID.fetch_add(1, Ordering::Relaxed);
+
+    PENDING_ACKS.lock().await.insert(
+        delivery_id,
+        PendingAck {
+            listener: listener.name.clone(),
+            durable: listener.durable,
+            callback: callback.clone(),
+            attempts: 1,
+            sent_at: Instant::now(),
+        },
+    );
+    metrics::PENDING_ACKS.inc();
+
+    delivery_id
+}
+
+/// Number of callbacks sent to the listener that it hasn't acknowledged yet
+pub async fn outstanding(listener: &str) -> usize {
+    PENDING_ACKS
+        .lock()
+        .await
+        .values()
+        .filter(|p| p.listener == listener)
+        .count()
+}
+
+/// Marks the delivery as processed, returns false when it isn't pending for the listener
+pub async fn acknowledge(delivery_id: u64, listener: &str) -> bool {
+    let mut pending = PENDING_ACKS.lock().await;
+
+    match pending.get(&delivery_id) {
+        Some(p) if p.listener == listener => {
+            pending.remove(&delivery_id);
+            metrics::PENDING_ACKS.dec();
+            true
+        }
+        _ => false,
+    }
+}
+
+/// Drops the pending callbacks of the event, only the ones of the listener when there is
+/// one, because it left the event or the event is gone
+pub async fn purge(event_name: &str, listener: Option<&str>) {
+    let mut pending = PENDING_ACKS.lock().await;
+    let before = pending.len();
+
+    pending.retain(|_, p| {
+        p.callback.event_name() != event_name || listener.is_some_and(|l| l != p.listener)
+    });
+    metrics::PENDING_ACKS.sub((before - pending.len()) as i64);
+}
+
+/// Sends again the callbacks that weren't acknowledged within `ack_timeout`. After
+/// `max_attempts` they are invoked on the dead-letter event instead
+pub async fn redeliver(ack_timeout: Duration, max_attempts: u32) {
+    let mut interval = tokio::time::interval((ack_timeout / 4).max(Duration::from_millis(10)));
+
+    loop {
+        interval.tick().await;
+
+        let writers = CLIENT_WRITERS.read().await;
+        let mut resend = Vec::new();
+        let mut dead = Vec::new();
+
+        {
+            let mut pending = PENDING_ACKS.lock().await;
+            let due: Vec<u64> = pending
+                .iter()
+                .filter(|(_, p)| p.sent_at.elapsed() >= ack_timeout)
+                .map(|(id, _)| *id)
+                .collect();
+
+            for delivery_id in due {
+                let p = pending.get_mut(&delivery_id).expect("Pending ack not found");
+
+                // It's queued for the listener, the timeout starts again when it reconnects
+                if p.durable && !writers.contains_key(&p.listener) {
+                    p.sent_at = Instant::now();
+                    continue;
+                }
+
+                if p.attempts >= max_attempts {
+                    dead.push((delivery_id, pending.remove(&delivery_id).unwrap()));
+                    metrics::PENDING_ACKS.dec();
+                    continue;
+                }
+
+                p.attempts += 1;
+                p.sent_at = Instant::now();
+                let frame = p.callback.frame(&[
+                    (DELIVERY_ID_HEADER, &delivery_id.to_string()),
+                    (REDELIVERED_HEADER, "true"),
+                ]);
+                resend.push((p.listener.clone(), frame));
+            }
+        }
+
+        for (listener, frame) in resend {
+            let Some(writer) = writers.get(&listener) else {
+                metrics::CALLBACKS_FAILED.with_label_values(&["disconnected"]).inc();
+                continue;
+            };
+
+            if let Err(e) = writer.lock().await.write_slice(&frame).await {
+                warn!("Failed to redeliver a callback to client {}: {}", listener, e);
+                metrics::CALLBACKS_FAILED.with_label_values(&["write_error"]).inc();
+                continue;
+            }
+
+            metrics::CALLBACKS_REDELIVERED.inc();
+            metrics::BYTES_OUT.inc_by(frame.len() as u64);
+        }
+        drop(writers);
+
+        for (delivery_id, p) in dead {
+            dead_letter(delivery_id, p).await;
+        }
+    }
+}
+
+async fn dead_letter(delivery_id: u64, pending: PendingAck) {
+    info!(
+        "callback {} of {} to {} moved to the dead-letter event after {} attempts",
+        delivery_id,
+        pending.callback.event_name(),
+        pending.listener,
+        pending.attempts
+    );
+    metrics::DEAD_LETTERS.inc();
+
+    let letter = DeadLetter {
+        delivery_id,
+        event: pending.callback.event_name(),
+        listener: &pending.listener,
+        caller: &pending.callback.caller,
+        attempts: pending.attempts,
+        body: String::from_utf8_lossy(&pending.callback.body),
+    };
+    registry::publish(DEAD_LETTER_EVENT, &letter).await;
+}
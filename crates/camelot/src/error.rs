@@ -12,4 +12,12 @@ pub enum Error {
     NoData,
     #[error("Connection closed")]
     ConexionClosed,
-}
\ No newline at end of file
+    #[error("Connection timed out")]
+    Timeout,
+    #[error("Frame of {0} bytes exceeds the maximum allowed length")]
+    FrameTooLarge(usize),
+    #[error("TLS error: {0}")]
+    TlsError(#[from] tokio_rustls::rustls::Error),
+    #[error("Invalid certificate or key: {0}")]
+    InvalidCertificate(String),
+}
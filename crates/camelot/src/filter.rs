@@ -0,0 +1,256 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::cmp::Ordering;
+
+const HEADER_PREFIX: &str = "header:";
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("The filter has an empty condition")]
+    EmptyCondition,
+    #[error("The condition `{0}` has no comparison operator")]
+    MissingOperator(String),
+    #[error("The condition `{0}` has no field")]
+    MissingField(String),
+}
+
+/// Subscription filter, a list of conditions joined by `&&` that all have to hold, e.g.
+/// `till == "T1" && total >= 10 && header:channel == web`.
+///
+/// The left side of a condition is a dotted path into the JSON body of the invoke, or
+/// `header:NAME` for one of its headers. The right side is a JSON value, and anything
+/// that isn't valid JSON is taken as a string. `&&` and the operators inside double
+/// quoted strings are part of the value
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct Filter {
+    source: String,
+    conditions: Vec<Condition>,
+}
+
+#[derive(Clone, Debug)]
+struct Condition {
+    field: Field,
+    operator: Operator,
+    value: Value,
+}
+
+#[derive(Clone, Debug)]
+enum Field {
+    Header(String),
+    Body(Vec<String>),
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Operator {
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Longest operators first, so `<=` isn't taken as `<`
+const OPERATORS: [(&str, Operator); 6] = [
+    ("==", Operator::Eq),
+    ("!=", Operator::Ne),
+    ("<=", Operator::Le),
+    (">=", Operator::Ge),
+    ("<", Operator::Lt),
+    (">", Operator::Gt),
+];
+
+impl Filter {
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut conditions = Vec::new();
+        let mut start = 0;
+        for i in unquoted(source) {
+            if i >= start && source[i..].starts_with("&&") {
+                conditions.push(Condition::parse(&source[start..i])?);
+                start = i + 2;
+            }
+        }
+        conditions.push(Condition::parse(&source[start..])?);
+
+        Ok(Self {
+            source: source.to_string(),
+            conditions,
+        })
+    }
+
+    /// Whether an invoke with these headers and body passes the filter. `body` is none
+    /// when the body isn't JSON, so only header conditions can hold
+    pub fn matches(&self, headers: &[(&str, &str)], body: Option<&Value>) -> bool {
+        self.conditions.iter().all(|c| c.matches(headers, body))
+    }
+}
+
+impl Condition {
+    fn parse(source: &str) -> Result<Self, Error> {
+        let source = source.trim();
+        if source.is_empty() {
+            return Err(Error::EmptyCondition);
+        }
+
+        let (position, token, operator) = unquoted(source)
+            .into_iter()
+            .find_map(|i| {
+                OPERATORS
+                    .iter()
+                    .find(|(token, _)| source[i..].starts_with(token))
+                    .map(|(token, operator)| (i, *token, *operator))
+            })
+            .ok_or_else(|| Error::MissingOperator(source.to_string()))?;
+
+        let field = source[..position].trim();
+        if field.is_empty() {
+            return Err(Error::MissingField(source.to_string()));
+        }
+
+        let field = match field.strip_prefix(HEADER_PREFIX) {
+            Some(header) => Field::Header(header.trim().to_string()),
+            None => Field::Body(field.split('.').map(str::to_string).collect()),
+        };
+
+        let value = source[position + token.len()..].trim();
+        let value = serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.to_string()));
+
+        Ok(Self {
+            field,
+            operator,
+            value,
+        })
+    }
+
+    fn matches(&self, headers: &[(&str, &str)], body: Option<&Value>) -> bool {
+        let header_value;
+        let actual = match &self.field {
+            Field::Header(name) => {
+                let Some((_, value)) = headers.iter().find(|(key, _)| key == name) else {
+                    return false;
+                };
+
+                // Headers are text, they are compared as numbers against numbers
+                header_value = match (&self.value, value.parse::<f64>()) {
+                    (Value::Number(_), Ok(number)) => Value::from(number),
+                    _ => Value::String(value.to_string()),
+                };
+                &header_value
+            }
+            Field::Body(path) => {
+                let Some(value) = body.and_then(|body| lookup(body, path)) else {
+                    return false;
+                };
+                value
+            }
+        };
+
+        match self.operator {
+            Operator::Eq => equals(actual, &self.value),
+            Operator::Ne => !equals(actual, &self.value),
+            Operator::Lt => compare(actual, &self.value) == Some(Ordering::Less),
+            Operator::Le => matches!(compare(actual, &self.value), Some(Ordering::Less | Ordering::Equal)),
+            Operator::Gt => compare(actual, &self.value) == Some(Ordering::Greater),
+            Operator::Ge => matches!(compare(actual, &self.value), Some(Ordering::Greater | Ordering::Equal)),
+        }
+    }
+}
+
+/// Byte offsets of the characters of `source` that aren't inside a double quoted string,
+/// so `&&` and the operators can be part of quoted values
+fn unquoted(source: &str) -> Vec<usize> {
+    let mut indices = Vec::new();
+    let (mut quoted, mut escaped) = (false, false);
+
+    for (i, c) in source.char_indices() {
+        if quoted {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => quoted = false,
+                _ => {}
+            }
+        } else if c == '"' {
+            quoted = true;
+        } else {
+            indices.push(i);
+        }
+    }
+
+    indices
+}
+
+fn lookup<'v>(body: &'v Value, path: &[String]) -> Option<&'v Value> {
+    path.iter().try_fold(body, |value, segment| match value {
+        Value::Object(map) => map.get(segment),
+        Value::Array(items) => items.get(segment.parse::<usize>().ok()?),
+        _ => None,
+    })
+}
+
+fn equals(left: &Value, right: &Value) -> bool {
+    match (left.as_f64(), right.as_f64()) {
+        (Some(l), Some(r)) => l == r,
+        _ => left == right,
+    }
+}
+
+fn compare(left: &Value, right: &Value) -> Option<Ordering> {
+    match (left, right) {
+        (Value::Number(l), Value::Number(r)) => l.as_f64()?.partial_cmp(&r.as_f64()?),
+        (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+        _ => None,
+    }
+}
+
+impl TryFrom<String> for Filter {
+    type Error = Error;
+
+    fn try_from(source: String) -> Result<Self, Self::Error> {
+        Filter::parse(&source)
+    }
+}
+
+impl From<Filter> for String {
+    fn from(filter: Filter) -> Self {
+        filter.source
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_filter_matches() {
+        let body = json!({"till": "T1", "total": 12.5, "lines": [{"sku": "A"}]});
+        let headers = [("channel", "web"), ("priority", "3")];
+
+        let passes = |filter: &str| Filter::parse(filter).unwrap().matches(&headers, Some(&body));
+
+        assert!(passes(r#"till == "T1""#));
+        assert!(passes("till == T1"));
+        assert!(passes("total > 10 && total <= 12.5"));
+        assert!(passes("lines.0.sku == A"));
+        assert!(passes("header:channel == web && header:priority >= 2"));
+        assert!(passes("till != T2"));
+        assert!(Filter::parse(r#"note == "a&&b""#).unwrap().matches(&[], Some(&json!({"note": "a&&b"}))));
+        let note = json!({"note": "x", "till": "T1"});
+        assert!(Filter::parse(r#"note != "x >= y" && till == T1"#).unwrap().matches(&[], Some(&note)));
+
+        assert!(!passes("till == T2"));
+        assert!(!passes("total < 10"));
+        assert!(!passes("missing == 1"));
+        assert!(!passes("header:missing == web"));
+        assert!(!passes("till == T1 && total > 20"));
+    }
+
+    #[test]
+    fn test_invalid_filters() {
+        assert!(matches!(Filter::parse("till"), Err(Error::MissingOperator(_))));
+        assert!(matches!(Filter::parse("== T1"), Err(Error::MissingField(_))));
+        assert!(matches!(Filter::parse("till == T1 &&"), Err(Error::EmptyCondition)));
+    }
+}
@@ -0,0 +1,39 @@
+use crate::delivery;
+use crate::handlers::ReqHandler;
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+pub(super) struct AckHandler;
+
+impl ReqHandler for AckHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+
+            let delivery_id = match request.head().header("delivery-id").map(str::parse::<u64>) {
+                Some(Ok(id)) => id,
+                _ => {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        "Missing or invalid delivery-id header".as_bytes(),
+                    );
+                }
+            };
+
+            if !delivery::acknowledge(delivery_id, caller_name).await {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    "No callback is waiting for that delivery-id".as_bytes(),
+                );
+            }
+
+            Response::new_ok(caller_name)
+        })
+    }
+}
@@ -0,0 +1,54 @@
+use crate::handlers::{is_admin, store_error_response, ReqHandler};
+use crate::scheduler::{self, SCHEDULE_ID_HEADER};
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+/// Cancels the schedule of the `schedule-id` header. Only the client that scheduled it,
+/// or an admin, can do it
+pub(super) struct CancelHandler;
+
+impl ReqHandler for CancelHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+
+            let Some(Ok(id)) = request.head().header(SCHEDULE_ID_HEADER).map(str::parse::<u64>) else {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    "The schedule-id header must be the id of a schedule".as_bytes(),
+                );
+            };
+
+            let not_found = || {
+                Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::ScheduleNotFound),
+                    "".as_bytes(),
+                )
+            };
+
+            let Some(schedule) = scheduler::get(id).await else {
+                return not_found();
+            };
+
+            if schedule.caller != caller_name && !is_admin(caller_name) {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::Forbidden),
+                    "Only the client that scheduled it or an admin can cancel it".as_bytes(),
+                );
+            }
+
+            match scheduler::cancel(id).await {
+                Ok(true) => Response::new_ok(caller_name),
+                Ok(false) => not_found(),
+                Err(e) => store_error_response(caller_name, e),
+            }
+        })
+    }
+}
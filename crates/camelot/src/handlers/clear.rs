@@ -0,0 +1,44 @@
+use crate::handlers::{is_admin, ReqHandler};
+use crate::registry::EVENTS;
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+/// Drops the retained value of the event. Only its creator or an admin can do it
+pub(super) struct ClearHandler;
+
+impl ReqHandler for ClearHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let event_name = format!("{}:{}", request.action().module(), request.action().id());
+
+            let guard = EVENTS.read().await;
+
+            let event = if let Some(e) = guard.get(&event_name) {
+                e
+            } else {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                    "".as_bytes(),
+                );
+            };
+
+            if event.metadata.creator != caller_name && !is_admin(caller_name) {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::Forbidden),
+                    "Only the creator of the event or an admin can clear it".as_bytes(),
+                );
+            }
+
+            event.retained_value.lock().unwrap().take();
+
+            Response::new_ok(caller_name)
+        })
+    }
+}
@@ -1,4 +1,7 @@
-use crate::handlers::{ReqHandler, EVENTS};
+use crate::handlers::{config, store_error_response, ReqHandler};
+use crate::registry::{self, Event, SystemNotice, EVENTS, EVENT_CREATED_EVENT};
+use crate::store;
+use serde_json::Value;
 use std::future::Future;
 use std::pin::Pin;
 use trtcp::{Head, Request, Response};
@@ -10,24 +13,92 @@ impl ReqHandler for CreateHandler {
         Box::pin(async move { 
             let event_name = format!("{}:{}", request.action().module(), request.action().id());
 
-            {
-                let guard = EVENTS.read().await;
-                
-                if guard.get(&event_name).is_some() {
+            let retention = match request.head().header("retention").map(str::parse::<usize>) {
+                None => 0,
+                Some(Ok(retention)) if retention <= config().max_retention => retention,
+                Some(Ok(_)) => {
+                    return Response::new_owned(
+                        Head::new_with_version(request.head().caller()),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        format!("The retention can't be over {} invokes", config().max_retention)
+                            .into_bytes(),
+                    );
+                }
+                Some(Err(_)) => {
                     return Response::new(
                         Head::new_with_version(request.head().caller()),
-                        trtcp::Status::new(trtcp::StatusType::EventAlreadyExists),
-                        "".as_bytes(),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        "The retention must be a number of invokes".as_bytes(),
                     );
-                };
+                }
+            };
+
+            // The body, when there is one, is the schema of the invokes
+            let schema = if request.body().is_empty() {
+                None
+            } else {
+                match serde_json::from_slice::<Value>(request.body()) {
+                    Ok(schema) => Some(schema),
+                    Err(e) => {
+                        return Response::new_owned(
+                            Head::new_with_version(request.head().caller()),
+                            trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                            format!("The schema isn't JSON: {}", e).into_bytes(),
+                        );
+                    }
+                }
+            };
+
+            if registry::is_pattern(&event_name) {
+                return Response::new(
+                    Head::new_with_version(request.head().caller()),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    "Event names can't contain *".as_bytes(),
+                );
             }
 
             {
+                // Checked under the write lock, so two creates of the same event can't both win
                 let mut guard = EVENTS.write().await;
-                guard.insert(event_name, Vec::new());
-                
-                Response::new_ok(request.head().caller())
+
+                if guard.contains_key(&event_name) {
+                    return Response::new(
+                        Head::new_with_version(request.head().caller()),
+                        trtcp::Status::new(trtcp::StatusType::EventAlreadyExists),
+                        "".as_bytes(),
+                    );
+                }
+
+                let mut event = Event::new(request.head().caller());
+                event.metadata.retained = request.head().header("retained") == Some("true");
+                event.metadata.retention = retention;
+
+                if let Some(schema) = schema {
+                    if let Err(e) = event.set_schema(schema) {
+                        return Response::new_owned(
+                            Head::new_with_version(request.head().caller()),
+                            trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                            format!("Invalid schema: {}", e).into_bytes(),
+                        );
+                    }
+                }
+
+                if let Err(e) = store::store().save_event(&event_name, &event.metadata) {
+                    return store_error_response(request.head().caller(), e);
+                }
+
+                registry::apply_patterns(&event_name, &mut event).await;
+                guard.insert(event_name.clone(), event);
             }
+
+            let notice = SystemNotice {
+                caller: request.head().caller(),
+                reason: "created",
+                event: Some(&event_name),
+            };
+            registry::publish(EVENT_CREATED_EVENT, &notice).await;
+
+            Response::new_ok(request.head().caller())
         })
     }
 }
\ No newline at end of file
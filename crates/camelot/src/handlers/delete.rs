@@ -0,0 +1,59 @@
+use crate::handlers::{is_admin, store_error_response, ReqHandler};
+use crate::registry::{self, EVENTS};
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+pub(super) struct DeleteHandler;
+
+impl ReqHandler for DeleteHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let event_name = format!("{}:{}", request.action().module(), request.action().id());
+
+            {
+                let guard = EVENTS.read().await;
+
+                let event = if let Some(e) = guard.get(&event_name) {
+                    e
+                } else {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                        "".as_bytes(),
+                    );
+                };
+
+                if registry::is_system_event(&event_name) {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::Forbidden),
+                        "System events can't be deleted".as_bytes(),
+                    );
+                }
+
+                if event.metadata.creator != caller_name && !is_admin(caller_name) {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::Forbidden),
+                        "Only the creator of the event or an admin can delete it".as_bytes(),
+                    );
+                }
+            }
+
+            match registry::delete_event(&event_name, caller_name).await {
+                Ok(true) => Response::new_ok(caller_name),
+                Ok(false) => Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                    "".as_bytes(),
+                ),
+                Err(e) => store_error_response(caller_name, e),
+            }
+        })
+    }
+}
@@ -0,0 +1,61 @@
+use crate::handlers::ReqHandler;
+use crate::registry::EVENTS;
+use serde::Serialize;
+use serde_json::Value;
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+pub(super) struct DescribeHandler;
+
+#[derive(Serialize)]
+struct Description<'a> {
+    event: &'a str,
+    creator: &'a str,
+    created_at: u64,
+    listeners: usize,
+    retained: bool,
+    retention: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<&'a Value>,
+}
+
+impl ReqHandler for DescribeHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let event_name = format!("{}:{}", request.action().module(), request.action().id());
+
+            let guard = EVENTS.read().await;
+
+            let event = if let Some(e) = guard.get(&event_name) {
+                e
+            } else {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                    "".as_bytes(),
+                );
+            };
+
+            let description = Description {
+                event: &event_name,
+                creator: &event.metadata.creator,
+                created_at: event.metadata.created_at,
+                listeners: event.listeners.len(),
+                retained: event.metadata.retained,
+                retention: event.metadata.retention,
+                schema: event.metadata.schema.as_ref(),
+            };
+
+            Response::new_owned(
+                Head::new_with_version(caller_name),
+                trtcp::Status::new(trtcp::StatusType::OK),
+                serde_json::to_vec(&description).expect("Could not serialize the event"),
+            )
+        })
+    }
+}
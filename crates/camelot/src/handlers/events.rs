@@ -0,0 +1,35 @@
+use crate::handlers::ReqHandler;
+use crate::registry::EVENTS;
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+pub(super) struct EventsHandler;
+
+impl ReqHandler for EventsHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let module = request.action().module();
+
+            // Every module when it's empty
+            let mut events: Vec<String> = EVENTS
+                .read()
+                .await
+                .keys()
+                .filter(|name| module.is_empty() || name.split_once(':').map(|(m, _)| m) == Some(module))
+                .cloned()
+                .collect();
+            events.sort();
+
+            Response::new_owned(
+                Head::new_with_version(caller_name),
+                trtcp::Status::new(trtcp::StatusType::OK),
+                serde_json::to_vec(&events).expect("Could not serialize the events"),
+            )
+        })
+    }
+}
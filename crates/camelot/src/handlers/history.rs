@@ -0,0 +1,83 @@
+use crate::handlers::{FollowUps, ReqHandler};
+use crate::registry::{EVENTS, SEQ_HEADER};
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+/// Streams the invokes kept by the retention of the event after the response, as callbacks
+/// with their `seq` and `timestamp` headers. The response body is the number of them.
+/// The `last: N` header limits them to the last N and `since: X` to the ones after the
+/// sequence number X
+#[derive(Default)]
+pub(super) struct HistoryHandler {
+    follow_ups: FollowUps,
+}
+
+impl ReqHandler for HistoryHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        let follow_ups = self.follow_ups.clone();
+
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let event_name = format!("{}:{}", request.action().module(), request.action().id());
+
+            let last = request.head().header("last").map(str::parse::<usize>);
+            let since = request.head().header("since").map(str::parse::<u64>);
+            if matches!(last, Some(Err(_))) || matches!(since, Some(Err(_))) {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    "The last and since headers must be numbers".as_bytes(),
+                );
+            }
+
+            let guard = EVENTS.read().await;
+
+            let event = if let Some(e) = guard.get(&event_name) {
+                e
+            } else {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                    "".as_bytes(),
+                );
+            };
+
+            let history = event.history.lock().unwrap();
+            let since = since.and_then(Result::ok).unwrap_or(0);
+            let entries: Vec<_> = history.iter().filter(|e| e.seq > since).collect();
+            let skip = match last.and_then(Result::ok) {
+                Some(last) => entries.len().saturating_sub(last),
+                None => 0,
+            };
+
+            let frames: Vec<Vec<u8>> = entries[skip..]
+                .iter()
+                .map(|entry| {
+                    entry.callback.frame(&[
+                        (SEQ_HEADER, &entry.seq.to_string()),
+                        ("timestamp", &entry.timestamp.to_string()),
+                        ("history", "true"),
+                    ])
+                })
+                .collect();
+
+            let count = frames.len().to_string();
+            *follow_ups.lock().unwrap() = frames;
+
+            // The body tells the caller how many callbacks follow
+            Response::new_owned(
+                Head::new_with_version(caller_name),
+                trtcp::Status::new(trtcp::StatusType::OK),
+                count.into_bytes(),
+            )
+        })
+    }
+
+    fn follow_ups(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut *self.follow_ups.lock().unwrap())
+    }
+}
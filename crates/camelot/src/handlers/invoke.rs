@@ -1,24 +1,55 @@
-use crate::handlers::{ReqHandler, EVENTS};
+use crate::delivery::{self, Callback, DeliveryReport};
+use crate::handlers::{config, ReqHandler};
+use crate::registry::{self, Event, Listener, RetainedValue, EVENTS};
+use crate::rpc::{self, REQUEST_ID_HEADER};
+use crate::scheduler::SCHEDULE_ID_HEADER;
 use crate::CLIENT_WRITERS;
+use serde::Serialize;
+use serde_json::Value;
+use std::borrow::Cow;
 use std::future::Future;
 use std::pin::Pin;
-use tracing::warn;
-use trtcp::{Action, ActionType, Head, Request, Response};
+use std::time::{Duration, Instant};
+use trtcp::{Head, Request, Response};
 
 pub(super) struct InvokeHandler;
 
 impl ReqHandler for InvokeHandler {
     fn handle<'a>(&self, request: &'a Request<'_>) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
         Box::pin(async move {
+            let received_at = Instant::now();
             let event_name = format!("{}:{}", request.action().module(), request.action().id());
             let caller_name = request.head().caller();
-            
-            {
+
+            let deadline = match request.head().header("deadline").map(str::parse::<u64>) {
+                None => Duration::from_millis(config().request_timeout_ms),
+                Some(Ok(ms)) => Duration::from_millis(ms.min(config().max_request_timeout_ms)),
+                Some(Err(_)) => {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        "The deadline must be a number of milliseconds".as_bytes(),
+                    );
+                }
+            };
+
+            if registry::is_system_event(&event_name) {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::Forbidden),
+                    "System events are only invoked by the broker".as_bytes(),
+                );
+            }
+
+            let gather = request.head().header("gather") == Some("true");
+            let wants_report = request.head().header("report") == Some("true");
+
+            let (gathering, report) = {
                 let events_guard = EVENTS.read().await;
 
-                let listeners = {
-                    if let Some(l) = events_guard.get(&event_name) {
-                        l
+                let event = {
+                    if let Some(e) = events_guard.get(&event_name) {
+                        e
                     } else {
                         return Response::new(
                             Head::new_with_version(request.head().caller()),
@@ -28,31 +59,278 @@ impl ReqHandler for InvokeHandler {
                     }
                 };
                 
-                let callback_request = Request::new(
-                    Head::new_with_version(caller_name),
-                    Action::new(ActionType::Callback, request.action().module(), request.action().id()),
-                    *request.body()
+                if let Err(violation) = event.validate(request.body()) {
+                    return Response::new_owned(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidPayload),
+                        serde_json::to_vec(&violation).expect("Could not serialize the violation"),
+                    );
+                }
+
+                let mut callback = Callback::new(
+                    caller_name,
+                    request.action().module(),
+                    request.action().id(),
+                    request.body(),
                 );
 
-                let call_bytes: Vec<u8> = callback_request.into();
-                
-                let guard = CLIENT_WRITERS.read().await;
-                for listener in listeners.iter() {
-                    let mut writer = if let Some(c) = guard.get(listener) {
-                        c.lock().await
-                    } else {
-                        // TODO: Remove the client name from the listeners 
-                        warn!("Client {} not found but is registered as a listener", listener);
-                        continue
+                if let Some(schedule_id) = request.head().header(SCHEDULE_ID_HEADER) {
+                    callback
+                        .headers
+                        .push((SCHEDULE_ID_HEADER.to_string(), schedule_id.to_string()));
+                }
+
+                let _sequence = event.sequencer.lock().await;
+                // Invokes meant for some listeners only aren't kept for the later ones
+                let targeted = request.head().header("to").is_some();
+                event.record(&event_name, &mut callback, !targeted);
+
+                if event.metadata.retained && !targeted {
+                    let headers = request
+                        .head()
+                        .headers()
+                        .iter()
+                        .map(|(key, value)| (key.to_string(), value.to_string()))
+                        .collect();
+                    *event.retained_value.lock().unwrap() = Some(RetainedValue {
+                        callback: callback.clone(),
+                        headers,
+                    });
+                }
+
+                let recipients = recipients(request, &event.listeners).await;
+                let recipients = apply_filters(request, recipients);
+                let recipients = pick_group_members(event, recipients).await;
+
+                // The caller is busy waiting for this response, so it can't reply itself
+                let gathering = if gather {
+                    let connected = CLIENT_WRITERS.read().await;
+                    let repliers: Vec<String> = recipients
+                        .iter()
+                        .filter(|l| l.name != caller_name && connected.contains_key(&l.name))
+                        .map(|l| l.name.clone())
+                        .collect();
+                    let missing: Vec<String> = recipients
+                        .iter()
+                        .filter(|l| l.name != caller_name && !repliers.contains(&l.name))
+                        .map(|l| l.name.clone())
+                        .collect();
+                    drop(connected);
+
+                    let (request_id, replies) = rpc::register(repliers.clone()).await;
+                    callback
+                        .headers
+                        .push((REQUEST_ID_HEADER.to_string(), request_id.to_string()));
+
+                    Some((request_id, replies, repliers, missing))
+                } else {
+                    None
+                };
+
+                let report = delivery::fan_out(&recipients, &callback, received_at).await;
+
+                (gathering, wants_report.then_some(report))
+            };
+
+            let Some((request_id, mut replies, mut repliers, mut missing)) = gathering else {
+                return match report {
+                    Some(report) => Response::new_owned(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::OK),
+                        serde_json::to_vec(&report).expect("Could not serialize the delivery report"),
+                    ),
+                    None => Response::new_ok(caller_name),
+                };
+            };
+
+            let mut gathered = Vec::new();
+            let wait = tokio::time::timeout(deadline, async {
+                while !repliers.is_empty() {
+                    let Some(reply) = replies.recv().await else {
+                        break;
                     };
-                    
-                    if let Err(e) = writer.write_slice(&call_bytes).await {
-                        warn!("Failed to send callback_request to client {}: {}", listener, e);
-                    }
+                    repliers.retain(|r| *r != reply.caller);
+                    gathered.push(reply);
                 }
-                
-                Response::new_ok(caller_name)
-            }
+            });
+            let _ = wait.await;
+            rpc::finish(request_id).await;
+
+            missing.extend(repliers);
+            let body = GatherResult {
+                replies: gathered
+                    .iter()
+                    .map(|reply| GatheredReply {
+                        caller: &reply.caller,
+                        body: String::from_utf8_lossy(&reply.body),
+                    })
+                    .collect(),
+                missing,
+                report,
+            };
+
+            Response::new_owned(
+                Head::new_with_version(caller_name),
+                trtcp::Status::new(trtcp::StatusType::OK),
+                serde_json::to_vec(&body).expect("Could not serialize the replies"),
+            )
         })
     }
-}
\ No newline at end of file
+}
+
+/// Response body of the invokes with `gather: true`
+#[derive(Serialize)]
+struct GatherResult<'r> {
+    replies: Vec<GatheredReply<'r>>,
+    /// Listeners that didn't reply before the deadline
+    missing: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    report: Option<DeliveryReport>,
+}
+
+#[derive(Serialize)]
+struct GatheredReply<'r> {
+    caller: &'r str,
+    body: Cow<'r, str>,
+}
+
+/// Applies the delivery options of the invoke to the listeners of the event, other than
+/// the responders:
+/// - `broadcast: true` adds every connected client, subscribed or not
+/// - `to: a,b` keeps only the named clients, connected ones included if they don't listen
+/// - `exclude-self: true` removes the caller
+async fn recipients<'l>(request: &Request<'_>, listeners: &'l [Listener]) -> Cow<'l, [Listener]> {
+    let head = request.head();
+    let broadcast = head.header("broadcast") == Some("true");
+    let exclude_self = head.header("exclude-self") == Some("true");
+    let to: Option<Vec<&str>> = head
+        .header("to")
+        .map(|names| names.split(',').map(str::trim).collect());
+
+    let responders = listeners.iter().any(|l| l.responder);
+
+    if !broadcast && !exclude_self && to.is_none() && !responders {
+        return Cow::Borrowed(listeners);
+    }
+
+    // Responders only receive requests
+    let mut recipients: Vec<Listener> = listeners.iter().filter(|l| !l.responder).cloned().collect();
+
+    if broadcast {
+        let connected = CLIENT_WRITERS.read().await;
+        let mut others: Vec<&String> = connected
+            .keys()
+            .filter(|name| !listeners.iter().any(|l| &l.name == *name))
+            .collect();
+        others.sort();
+
+        recipients.extend(others.into_iter().map(|name| Listener {
+            name: name.clone(),
+            ..Default::default()
+        }));
+    }
+
+    if let Some(to) = to {
+        recipients.retain(|l| to.contains(&l.name.as_str()));
+
+        // Named clients reach the terminal whether it listens to the event or not
+        let connected = CLIENT_WRITERS.read().await;
+        let mut others: Vec<&str> = to
+            .iter()
+            .copied()
+            .filter(|name| connected.contains_key(*name))
+            .filter(|name| !listeners.iter().any(|l| l.name == *name))
+            .collect();
+        others.sort();
+        others.dedup();
+
+        recipients.extend(others.into_iter().map(|name| Listener {
+            name: name.to_string(),
+            ..Default::default()
+        }));
+    }
+
+    if exclude_self {
+        recipients.retain(|l| l.name != head.caller());
+    }
+
+    Cow::Owned(recipients)
+}
+
+/// Removes the recipients whose filter the invoke doesn't pass
+fn apply_filters<'l>(request: &Request<'_>, recipients: Cow<'l, [Listener]>) -> Cow<'l, [Listener]> {
+    if recipients.iter().all(|l| l.filter.is_none()) {
+        return recipients;
+    }
+
+    let body: Option<Value> = serde_json::from_slice(request.body()).ok();
+    let headers = request.head().headers();
+
+    Cow::Owned(
+        recipients
+            .iter()
+            .filter(|l| l.filter.as_ref().is_none_or(|f| f.matches(headers, body.as_ref())))
+            .cloned()
+            .collect(),
+    )
+}
+
+/// Leaves one member of every consumer group among the recipients. Groups in ack mode pick
+/// the member with the least unacknowledged callbacks, the others take turns. Connected
+/// members go first, so the callback only waits in a queue when the whole group is away
+async fn pick_group_members<'l>(event: &Event, recipients: Cow<'l, [Listener]>) -> Cow<'l, [Listener]> {
+    if recipients.iter().all(|l| l.group.is_none()) {
+        return recipients;
+    }
+
+    let mut groups: Vec<(&str, Vec<&Listener>)> = Vec::new();
+    for listener in recipients.iter() {
+        let Some(group) = listener.group.as_deref() else {
+            continue;
+        };
+
+        match groups.iter_mut().find(|(name, _)| *name == group) {
+            Some((_, members)) => members.push(listener),
+            None => groups.push((group, vec![listener])),
+        }
+    }
+
+    let mut chosen = Vec::new();
+    {
+        let connected = CLIENT_WRITERS.read().await;
+
+        for (group, members) in groups {
+            let online: Vec<&Listener> = members
+                .iter()
+                .copied()
+                .filter(|l| connected.contains_key(&l.name))
+                .collect();
+            let candidates = if online.is_empty() { members } else { online };
+
+            let member = if candidates.iter().all(|l| l.ack) {
+                let mut least = candidates[0];
+                let mut least_outstanding = delivery::outstanding(&least.name).await;
+                for candidate in &candidates[1..] {
+                    let outstanding = delivery::outstanding(&candidate.name).await;
+                    if outstanding < least_outstanding {
+                        least = candidate;
+                        least_outstanding = outstanding;
+                    }
+                }
+                least
+            } else {
+                candidates[event.next_group_turn(group, candidates.len())]
+            };
+
+            chosen.push(member.name.clone());
+        }
+    }
+
+    Cow::Owned(
+        recipients
+            .iter()
+            .filter(|l| l.group.is_none() || chosen.contains(&l.name))
+            .cloned()
+            .collect(),
+    )
+}
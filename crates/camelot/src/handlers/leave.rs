@@ -1,4 +1,7 @@
-use crate::handlers::{ReqHandler, EVENTS};
+use crate::delivery;
+use crate::handlers::{store_error_response, ReqHandler};
+use crate::registry::{self, EVENTS, PATTERNS};
+use crate::store;
 use std::future::Future;
 use std::pin::Pin;
 use trtcp::{Head, Request, Response};
@@ -14,11 +17,15 @@ impl ReqHandler for LeaveHandler {
             let caller_name = request.head().caller();
             let event_name = format!("{}:{}", request.action().module(), request.action().id());
 
+            if registry::is_pattern(&event_name) {
+                return leave_pattern(caller_name, &event_name).await;
+            }
+
             let item_position = {
                 let guard = EVENTS.read().await;
 
-                let listeners = if let Some(l) = guard.get(&event_name) {
-                    l
+                let event = if let Some(e) = guard.get(&event_name) {
+                    e
                 } else {
                     return Response::new(
                         Head::new_with_version(caller_name),
@@ -27,7 +34,7 @@ impl ReqHandler for LeaveHandler {
                     );
                 };
 
-                if let Some(p) = listeners.iter().position(|l| l == caller_name) {
+                if let Some(p) = event.listener_position(caller_name) {
                     p
                 } else {
                     return Response::new(
@@ -40,8 +47,8 @@ impl ReqHandler for LeaveHandler {
 
             {
                 let mut guard = EVENTS.write().await;
-                let listeners = if let Some(vec) = guard.get_mut(&event_name) {
-                    vec
+                let event = if let Some(e) = guard.get_mut(&event_name) {
+                    e
                 } else {
                     return Response::new_unexpected_error(
                         caller_name,
@@ -49,10 +56,90 @@ impl ReqHandler for LeaveHandler {
                     );
                 };
 
-                listeners.swap_remove(item_position);
+                if event.listeners[item_position].durable {
+                    if let Err(e) = store::store().delete_subscription(&event_name, caller_name) {
+                        return store_error_response(caller_name, e);
+                    }
+                }
+
+                event.listeners.swap_remove(item_position);
             }
+            delivery::purge(&event_name, Some(caller_name)).await;
 
             Response::new_ok(caller_name)
         })
     }
 }
+
+/// Removes the pattern subscription and the listeners it added to the events
+async fn leave_pattern<'a>(caller_name: &'a str, pattern: &str) -> Response<'a> {
+    let left = match remove_pattern_listener(caller_name, pattern).await {
+        Ok(left) => left,
+        Err(response) => return response,
+    };
+
+    for event_name in left {
+        delivery::purge(&event_name, Some(caller_name)).await;
+    }
+
+    Response::new_ok(caller_name)
+}
+
+/// Returns the events the caller doesn't listen to anymore
+async fn remove_pattern_listener<'a>(
+    caller_name: &'a str,
+    pattern: &str,
+) -> Result<Vec<String>, Response<'a>> {
+    let mut events = EVENTS.write().await;
+    let mut patterns = PATTERNS.write().await;
+
+    let Some(listeners) = patterns.get_mut(pattern) else {
+        return Err(Response::new(
+            Head::new_with_version(caller_name),
+            trtcp::Status::new(trtcp::StatusType::ListenerNotFound),
+            "".as_bytes(),
+        ));
+    };
+
+    let Some(position) = listeners.iter().position(|l| l.name == caller_name) else {
+        return Err(Response::new(
+            Head::new_with_version(caller_name),
+            trtcp::Status::new(trtcp::StatusType::ListenerNotFound),
+            "".as_bytes(),
+        ));
+    };
+
+    if listeners[position].durable {
+        if let Err(e) = store::store().delete_subscription(pattern, caller_name) {
+            return Err(store_error_response(caller_name, e));
+        }
+    }
+
+    listeners.remove(position);
+    if listeners.is_empty() {
+        patterns.remove(pattern);
+    }
+
+    let mut left = Vec::new();
+    for (event_name, event) in events.iter_mut() {
+        let before = event.listeners.len();
+        event
+            .listeners
+            .retain(|l| l.name != caller_name || l.pattern.as_deref() != Some(pattern));
+        if event.listeners.len() == before {
+            continue;
+        }
+
+        // Another pattern of the caller may still match the event
+        let remaining = patterns.iter().find_map(|(other, listeners)| {
+            let listener = listeners.iter().find(|l| l.name == caller_name)?;
+            registry::matches(other, event_name).then_some(listener)
+        });
+        match remaining {
+            Some(listener) => event.add_pattern_listener(listener),
+            None => left.push(event_name.clone()),
+        }
+    }
+
+    Ok(left)
+}
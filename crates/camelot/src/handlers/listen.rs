@@ -1,24 +1,61 @@
-use crate::handlers::{ReqHandler, EVENTS};
+use crate::filter::Filter;
+use crate::handlers::{store_error_response, FollowUps, HeldWriter, ReqHandler};
+use crate::registry::{self, Listener, RetainedValue, EVENTS, PATTERNS};
+use crate::{store, CLIENT_WRITERS};
+use serde_json::Value;
 use std::future::Future;
 use std::pin::Pin;
+use std::sync::{Arc, Mutex};
 use trtcp::{Head, Request, Response};
 
-pub(super) struct ListenHandler;
+#[derive(Default)]
+pub(super) struct ListenHandler {
+    follow_ups: FollowUps,
+    writer: Arc<Mutex<Option<HeldWriter>>>,
+}
 
 impl ReqHandler for ListenHandler {
     fn handle<'a>(
         &self,
         request: &'a Request<'_>,
     ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        let follow_ups = self.follow_ups.clone();
+        let held_writer = self.writer.clone();
+
         Box::pin(async move {
             let caller_name = request.head().caller();
             let event_name = format!("{}:{}", request.action().module(), request.action().id());
 
+            let filter = match request.head().header("filter").map(Filter::parse).transpose() {
+                Ok(filter) => filter,
+                Err(e) => {
+                    return Response::new_owned(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        e.to_string().into_bytes(),
+                    );
+                }
+            };
+
+            let listener = Listener {
+                name: caller_name.to_string(),
+                durable: request.head().header("durable") == Some("true"),
+                ack: request.head().header("delivery") == Some("ack"),
+                responder: request.head().header("responder") == Some("true"),
+                group: request.head().header("group").map(str::to_string),
+                filter,
+                pattern: None,
+            };
+
+            if registry::is_pattern(&event_name) {
+                return listen_pattern(caller_name, event_name, listener).await;
+            }
+
             let already_subscribed = {
                 let guard = EVENTS.read().await;
 
-                let listeners = if let Some(l) = guard.get(&event_name) {
-                    l
+                let event = if let Some(e) = guard.get(&event_name) {
+                    e
                 } else {
                     return Response::new(
                         Head::new_with_version(caller_name),
@@ -27,7 +64,7 @@ impl ReqHandler for ListenHandler {
                     );
                 };
                 
-                listeners.iter().any(|l| l == caller_name)
+                event.listener_position(caller_name).is_some()
             };
             
             if already_subscribed {
@@ -40,8 +77,8 @@ impl ReqHandler for ListenHandler {
 
             {
                 let mut guard = EVENTS.write().await;
-                let listeners = if let Some(l) = guard.get_mut(&event_name) {
-                    l
+                let event = if let Some(e) = guard.get_mut(&event_name) {
+                    e
                 } else {
                     return Response::new(
                         Head::new_with_version(caller_name),
@@ -49,17 +86,107 @@ impl ReqHandler for ListenHandler {
                         "".as_bytes(),
                     );
                 };
-                listeners.push(caller_name.to_string());
+                if listener.durable {
+                    if let Err(e) = store::store().save_subscription(&event_name, &listener) {
+                        return store_error_response(caller_name, e);
+                    }
+                }
+
+                let retained = event
+                    .retained_value
+                    .lock()
+                    .unwrap()
+                    .clone()
+                    .filter(|retained| gets_retained(&listener, retained));
+                event.listeners.push(listener);
+
+                // The new listener gets the retained value right after the response. Its writer
+                // is locked while the events still are, so a newer invoke can't get ahead of it
+                if let Some(RetainedValue { callback, .. }) = retained {
+                    let writer = CLIENT_WRITERS.read().await.get(caller_name).cloned();
+                    if let Some(writer) = writer {
+                        *held_writer.lock().unwrap() = Some(writer.lock_owned().await);
+                    }
+
+                    let frame = callback.frame(&[("retained", "true")]);
+                    follow_ups.lock().unwrap().push(frame);
+                }
+
                 Response::new_ok(caller_name)
             }
         })
     }
+
+    fn follow_ups(&self) -> Vec<Vec<u8>> {
+        std::mem::take(&mut *self.follow_ups.lock().unwrap())
+    }
+
+    fn held_writer(&self) -> Option<HeldWriter> {
+        self.writer.lock().unwrap().take()
+    }
+}
+
+/// Whether the listener would have gotten the retained invoke, responders and listeners
+/// whose filter it doesn't pass don't get it
+fn gets_retained(listener: &Listener, retained: &RetainedValue) -> bool {
+    if listener.responder {
+        return false;
+    }
+
+    let Some(filter) = &listener.filter else {
+        return true;
+    };
+
+    let headers: Vec<(&str, &str)> = retained
+        .headers
+        .iter()
+        .map(|(key, value)| (key.as_str(), value.as_str()))
+        .collect();
+    let body: Option<Value> = serde_json::from_slice(&retained.callback.body).ok();
+
+    filter.matches(&headers, body.as_ref())
+}
+
+/// Subscribes to every event matching the pattern, the existing ones and the ones created later
+async fn listen_pattern(caller_name: &str, pattern: String, listener: Listener) -> Response<'_> {
+    let mut events = EVENTS.write().await;
+    let mut patterns = PATTERNS.write().await;
+
+    let subscribed = patterns
+        .get(&pattern)
+        .is_some_and(|listeners| listeners.iter().any(|l| l.name == caller_name));
+    if subscribed {
+        return Response::new(
+            Head::new_with_version(caller_name),
+            trtcp::Status::new(trtcp::StatusType::AlreadySubscribed),
+            "".as_bytes(),
+        );
+    }
+
+    let listener = Listener {
+        pattern: Some(pattern.clone()),
+        ..listener
+    };
+
+    if listener.durable {
+        if let Err(e) = store::store().save_subscription(&pattern, &listener) {
+            return store_error_response(caller_name, e);
+        }
+    }
+
+    for (name, event) in events.iter_mut() {
+        if registry::matches(&pattern, name) {
+            event.add_pattern_listener(&listener);
+        }
+    }
+    patterns.entry(pattern).or_default().push(listener);
+
+    Response::new_ok(caller_name)
 }
 
 #[cfg(test)]
 mod test {
     use super::*;
-    use crate::handlers::EVENTS;
     use trtcp::{Action, ActionType, Head, Request, StatusType, Version};
 
     #[tokio::test]
@@ -70,7 +197,7 @@ mod test {
             "".as_bytes(),
         );
 
-        let response = ListenHandler.handle(&request).await;
+        let response = ListenHandler::default().handle(&request).await;
 
         assert_eq!(*response.status().r#type(), StatusType::EventNotFound);
 
@@ -1,25 +1,77 @@
-use std::collections::HashMap;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::{Arc, LazyLock};
-use tokio::sync::RwLock;
+use std::sync::{Arc, Mutex, OnceLock};
+use camelot::WriteHalfClient;
+use crate::config::Config;
+use clap::Parser;
+use crate::metrics;
+use tokio::sync::OwnedMutexGuard;
 use trtcp::{Response, StatusType, Version};
 
+mod ack;
+mod clear;
+mod history;
 mod invoke;
 mod create;
+mod delete;
+mod describe;
+mod events;
 mod invalid;
 mod leave;
 mod listen;
+mod reply;
+mod request;
+mod subscriptions;
 mod callback;
-
-static EVENTS: LazyLock<Arc<RwLock<HashMap<String, Vec<String>>>>> =
-    LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
+mod cancel;
+mod schedule;
+mod schedules;
 
 trait ReqHandler: Send {
     fn handle<'a>(
         &self,
         request: &'a trtcp::Request<'_>,
     ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>>;
+
+    /// Frames to write to the caller right after the response, once the request is handled
+    fn follow_ups(&self) -> Vec<Vec<u8>> {
+        Vec::new()
+    }
+
+    /// Writer of the caller the handler locked while the registry was still locked, so no
+    /// callback gets between the follow-ups and the state they were taken from
+    fn held_writer(&self) -> Option<HeldWriter> {
+        None
+    }
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+pub fn init(config: &Config) {
+    let _ = CONFIG.set(config.clone());
+}
+
+/// Configuration the broker started with, the default one if the handlers weren't initialized
+pub fn config() -> &'static Config {
+    CONFIG.get_or_init(|| Config::parse_from(["camelot"]))
+}
+
+/// Whether the caller can manage events created by others
+fn is_admin(caller: &str) -> bool {
+    config().admins.iter().any(|a| a == caller)
+}
+
+/// Frames a handler leaves for the caller while it handles the request
+type FollowUps = Arc<Mutex<Vec<Vec<u8>>>>;
+
+type HeldWriter = OwnedMutexGuard<WriteHalfClient>;
+
+/// What is written to the caller after the response
+#[derive(Default)]
+pub struct FollowUp {
+    pub frames: Vec<Vec<u8>>,
+    /// Writer to write the response and the frames with, instead of locking it again
+    pub writer: Option<HeldWriter>,
 }
 
 impl From<&trtcp::ActionType> for Box<dyn ReqHandler> {
@@ -28,16 +80,35 @@ impl From<&trtcp::ActionType> for Box<dyn ReqHandler> {
             trtcp::ActionType::Connect => {
                 Box::from(invalid::InvalidHandler::new(StatusType::AlreadyConnected))
             }
-            trtcp::ActionType::Listen => Box::from(listen::ListenHandler),
+            trtcp::ActionType::Listen => Box::from(listen::ListenHandler::default()),
             trtcp::ActionType::Invoke => Box::from(invoke::InvokeHandler),
             trtcp::ActionType::Leave => Box::from(leave::LeaveHandler),
             trtcp::ActionType::Create => Box::from(create::CreateHandler),
             &trtcp::ActionType::Callback => Box::from(callback::CallbackHandler),
+            trtcp::ActionType::Ack => Box::from(ack::AckHandler),
+            trtcp::ActionType::Clear => Box::from(clear::ClearHandler),
+            trtcp::ActionType::History => Box::from(history::HistoryHandler::default()),
+            trtcp::ActionType::Delete => Box::from(delete::DeleteHandler),
+            trtcp::ActionType::Request => Box::from(request::RequestHandler),
+            trtcp::ActionType::Reply => Box::from(reply::ReplyHandler),
+            trtcp::ActionType::Events => Box::from(events::EventsHandler),
+            trtcp::ActionType::Describe => Box::from(describe::DescribeHandler),
+            trtcp::ActionType::Subscriptions => Box::from(subscriptions::SubscriptionsHandler),
+            trtcp::ActionType::Schedule => Box::from(schedule::ScheduleHandler),
+            trtcp::ActionType::Schedules => Box::from(schedules::SchedulesHandler),
+            trtcp::ActionType::Cancel => Box::from(cancel::CancelHandler),
+            // Only persistent connections can end cleanly, they handle it themselves
+            trtcp::ActionType::Disconnect => {
+                Box::from(invalid::InvalidHandler::new(StatusType::NeedConnection))
+            }
         }
     }
 }
 
-pub async fn handle_request<'a>(request: &'a trtcp::Request<'_>) -> Response<'a> {
+/// Handles the request, returning its response and the frames to write after it
+pub async fn handle_request<'a>(
+    request: &'a trtcp::Request<'_>,
+) -> (Response<'a>, FollowUp) {
     let version = request.head().version();
     if *version.major() != *Version::actual().major() {
         panic!(
@@ -48,5 +119,27 @@ pub async fn handle_request<'a>(request: &'a trtcp::Request<'_>) -> Response<'a>
     }
 
     let handler: Box<dyn ReqHandler> = request.action().r#type().into();
-    handler.handle(&request).await
+    let response = handler.handle(request).await;
+
+    metrics::REQUESTS
+        .with_label_values(&[request.action().r#type().name(), response.status().r#type().name()])
+        .inc();
+
+    let follow_up = FollowUp {
+        frames: handler.follow_ups(),
+        writer: handler.held_writer(),
+    };
+
+    (response, follow_up)
+}
+
+/// Response for the requests whose changes couldn't be saved by the store
+fn store_error_response<'a>(caller: &'a str, error: crate::store::Error) -> Response<'a> {
+    tracing::error!("store error: {}", error);
+
+    Response::new(
+        trtcp::Head::new_with_version(caller),
+        trtcp::Status::new(StatusType::InternalServerError),
+        "Could not persist the change".as_bytes(),
+    )
 }
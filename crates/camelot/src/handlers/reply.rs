@@ -0,0 +1,39 @@
+use crate::handlers::ReqHandler;
+use crate::rpc::{self, REQUEST_ID_HEADER};
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+pub(super) struct ReplyHandler;
+
+impl ReqHandler for ReplyHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+
+            let request_id = match request.head().header(REQUEST_ID_HEADER).map(str::parse::<u64>) {
+                Some(Ok(id)) => id,
+                _ => {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        "Missing or invalid request-id header".as_bytes(),
+                    );
+                }
+            };
+
+            if !rpc::reply(request_id, caller_name, request.body()).await {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    "Nothing is waiting for that reply".as_bytes(),
+                );
+            }
+
+            Response::new_ok(caller_name)
+        })
+    }
+}
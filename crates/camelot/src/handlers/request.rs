@@ -0,0 +1,110 @@
+use crate::delivery::{self, Callback};
+use crate::handlers::{config, ReqHandler};
+use crate::registry::EVENTS;
+use crate::rpc::{self, REQUEST_ID_HEADER};
+use crate::CLIENT_WRITERS;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use tracing::debug;
+use trtcp::{Head, Request, Response};
+
+/// Sends the request to one responder of the event and answers with its reply. The
+/// `timeout` header, in milliseconds, overrides the default of the broker up to its maximum
+pub(super) struct RequestHandler;
+
+impl ReqHandler for RequestHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let event_name = format!("{}:{}", request.action().module(), request.action().id());
+
+            let timeout = match request.head().header("timeout").map(str::parse::<u64>) {
+                None => Duration::from_millis(config().request_timeout_ms),
+                Some(Ok(ms)) => Duration::from_millis(ms.min(config().max_request_timeout_ms)),
+                Some(Err(_)) => {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        "The timeout must be a number of milliseconds".as_bytes(),
+                    );
+                }
+            };
+
+            let responder = {
+                let events = EVENTS.read().await;
+                let Some(event) = events.get(&event_name) else {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                        "".as_bytes(),
+                    );
+                };
+
+                if let Err(violation) = event.validate(request.body()) {
+                    return Response::new_owned(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidPayload),
+                        serde_json::to_vec(&violation).expect("Could not serialize the violation"),
+                    );
+                }
+
+                let connected = CLIENT_WRITERS.read().await;
+                event
+                    .next_responder(|name| connected.contains_key(name))
+                    .map(|l| l.name.clone())
+            };
+
+            let Some(responder) = responder else {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::ListenerNotFound),
+                    "No responder is connected".as_bytes(),
+                );
+            };
+
+            let (request_id, mut replies) = rpc::register(vec![responder.clone()]).await;
+
+            let mut callback = Callback::new(
+                caller_name,
+                request.action().module(),
+                request.action().id(),
+                request.body(),
+            );
+            callback
+                .headers
+                .push((REQUEST_ID_HEADER.to_string(), request_id.to_string()));
+
+            if !delivery::send(&responder, &callback.frame(&[])).await {
+                rpc::finish(request_id).await;
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::ListenerNotFound),
+                    "The request couldn't be sent to the responder".as_bytes(),
+                );
+            }
+
+            let reply = tokio::time::timeout(timeout, replies.recv()).await;
+            rpc::finish(request_id).await;
+
+            match reply {
+                Ok(Some(reply)) => {
+                    debug!("request {} of {} answered by {}", request_id, caller_name, reply.caller);
+                    Response::new_owned(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::OK),
+                        reply.body,
+                    )
+                }
+                _ => Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::Timeout),
+                    "The responder didn't reply in time".as_bytes(),
+                ),
+            }
+        })
+    }
+}
@@ -0,0 +1,87 @@
+use crate::handlers::{store_error_response, ReqHandler};
+use crate::registry::{self, EVENTS};
+use crate::scheduler;
+use std::future::Future;
+use std::pin::Pin;
+use std::time::Duration;
+use trtcp::{Head, Request, Response};
+
+/// Schedules an invoke of the event with the body of the request, after the `delay: MS`
+/// header or on every occurrence of the `cron: EXPRESSION` header. The response body is
+/// the id of the schedule
+pub(super) struct ScheduleHandler;
+
+impl ReqHandler for ScheduleHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+            let event_name = format!("{}:{}", request.action().module(), request.action().id());
+
+            let delay = match request.head().header("delay").map(str::parse::<u64>) {
+                None => None,
+                Some(Ok(ms)) => Some(Duration::from_millis(ms)),
+                Some(Err(_)) => {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                        "The delay must be a number of milliseconds".as_bytes(),
+                    );
+                }
+            };
+            let cron = request.head().header("cron");
+
+            if delay.is_some() == cron.is_some() {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    "A schedule needs either a delay or a cron header".as_bytes(),
+                );
+            }
+
+            if registry::is_system_event(&event_name) {
+                return Response::new(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::Forbidden),
+                    "System events are only invoked by the broker".as_bytes(),
+                );
+            }
+
+            {
+                let events = EVENTS.read().await;
+                let Some(event) = events.get(&event_name) else {
+                    return Response::new(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::EventNotFound),
+                        "".as_bytes(),
+                    );
+                };
+
+                // The body doesn't change, a bad one would fail on every run
+                if let Err(violation) = event.validate(request.body()) {
+                    return Response::new_owned(
+                        Head::new_with_version(caller_name),
+                        trtcp::Status::new(trtcp::StatusType::InvalidPayload),
+                        serde_json::to_vec(&violation).expect("Could not serialize the violation"),
+                    );
+                }
+            }
+
+            match scheduler::add(caller_name, &event_name, request.body(), delay, cron).await {
+                Ok(schedule) => Response::new_owned(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::OK),
+                    schedule.id.to_string().into_bytes(),
+                ),
+                Err(scheduler::Error::Store(e)) => store_error_response(caller_name, e),
+                Err(e) => Response::new_owned(
+                    Head::new_with_version(caller_name),
+                    trtcp::Status::new(trtcp::StatusType::InvalidRequest),
+                    e.to_string().into_bytes(),
+                ),
+            }
+        })
+    }
+}
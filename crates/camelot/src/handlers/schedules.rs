@@ -0,0 +1,55 @@
+use crate::handlers::{is_admin, ReqHandler};
+use crate::scheduler::{self, Schedule};
+use serde::Serialize;
+use std::borrow::Cow;
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+/// Lists the schedules of the caller, every one of them for admins
+pub(super) struct SchedulesHandler;
+
+#[derive(Serialize)]
+struct ScheduleView<'a> {
+    id: u64,
+    event: &'a str,
+    caller: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    cron: Option<&'a str>,
+    next_run: u64,
+    body: Cow<'a, str>,
+}
+
+impl<'a> From<&'a Schedule> for ScheduleView<'a> {
+    fn from(schedule: &'a Schedule) -> Self {
+        Self {
+            id: schedule.id,
+            event: &schedule.event,
+            caller: &schedule.caller,
+            cron: schedule.cron.as_deref(),
+            next_run: schedule.next_run,
+            body: String::from_utf8_lossy(&schedule.body),
+        }
+    }
+}
+
+impl ReqHandler for SchedulesHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+
+            let owner = (!is_admin(caller_name)).then_some(caller_name);
+            let schedules = scheduler::list(owner).await;
+            let views: Vec<ScheduleView> = schedules.iter().map(ScheduleView::from).collect();
+
+            Response::new_owned(
+                Head::new_with_version(caller_name),
+                trtcp::Status::new(trtcp::StatusType::OK),
+                serde_json::to_vec(&views).expect("Could not serialize the schedules"),
+            )
+        })
+    }
+}
@@ -0,0 +1,52 @@
+use crate::handlers::ReqHandler;
+use crate::registry::{Listener, EVENTS, PATTERNS};
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use trtcp::{Head, Request, Response};
+
+pub(super) struct SubscriptionsHandler;
+
+/// Event or pattern the caller listens to, with the options of the subscription
+#[derive(Serialize)]
+struct Subscription<'a> {
+    event: &'a str,
+    #[serde(flatten)]
+    listener: &'a Listener,
+}
+
+impl ReqHandler for SubscriptionsHandler {
+    fn handle<'a>(
+        &self,
+        request: &'a Request<'_>,
+    ) -> Pin<Box<dyn Future<Output = Response<'a>> + Send + 'a>> {
+        Box::pin(async move {
+            let caller_name = request.head().caller();
+
+            let events = EVENTS.read().await;
+            let patterns = PATTERNS.read().await;
+
+            // The listeners copied from a pattern are only listed as the pattern
+            let own = events.iter().filter_map(|(name, event)| {
+                let listener = event
+                    .listeners
+                    .iter()
+                    .find(|l| l.name == caller_name && l.pattern.is_none())?;
+                Some(Subscription { event: name, listener })
+            });
+            let by_pattern = patterns.iter().filter_map(|(pattern, listeners)| {
+                let listener = listeners.iter().find(|l| l.name == caller_name)?;
+                Some(Subscription { event: pattern, listener })
+            });
+
+            let mut subscriptions: Vec<Subscription> = own.chain(by_pattern).collect();
+            subscriptions.sort_by_key(|s| s.event);
+
+            Response::new_owned(
+                Head::new_with_version(caller_name),
+                trtcp::Status::new(trtcp::StatusType::OK),
+                serde_json::to_vec(&subscriptions).expect("Could not serialize the subscriptions"),
+            )
+        })
+    }
+}
@@ -0,0 +1,221 @@
+use crate::handlers;
+use crate::registry::{Event, Listener, EVENTS};
+use crate::CLIENT_WRITERS;
+use axum::extract::{FromRequestParts, Path};
+use axum::http::request::Parts;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::get;
+use axum::{Json, Router};
+use bytes::Bytes;
+use serde::Serialize;
+use serde_json::Value;
+use std::net::SocketAddr;
+use tokio::net::TcpListener;
+use tracing::{error, info};
+use trtcp::{Action, ActionType, Head, Request, StatusType};
+
+/// Caller of the requests made through the API when it runs without tokens
+const DEFAULT_CALLER: &str = "http";
+/// Header with the trtcp status of the request, the HTTP status is only an approximation
+const STATUS_HEADER: &str = "x-trtcp-status";
+
+#[derive(Serialize)]
+struct EventView {
+    event: String,
+    creator: String,
+    created_at: u64,
+    retained: bool,
+    retention: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    schema: Option<Value>,
+    listeners: Vec<Listener>,
+}
+
+impl EventView {
+    fn new(name: &str, event: &Event) -> Self {
+        Self {
+            event: name.to_string(),
+            creator: event.metadata.creator.clone(),
+            created_at: event.metadata.created_at,
+            retained: event.metadata.retained,
+            retention: event.metadata.retention,
+            schema: event.metadata.schema.clone(),
+            listeners: event.listeners.clone(),
+        }
+    }
+}
+
+/// Caller the request acts as, the one of its bearer token
+struct Caller(String);
+
+impl<S: Send + Sync> FromRequestParts<S> for Caller {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        let tokens = &handlers::config().http_tokens;
+        if tokens.is_empty() {
+            return Ok(Caller(DEFAULT_CALLER.to_string()));
+        }
+
+        let token = parts
+            .headers
+            .get(header::AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.strip_prefix("Bearer "))
+            .ok_or(StatusCode::UNAUTHORIZED)?;
+
+        tokens
+            .iter()
+            .find(|t| t.token == token)
+            .map(|t| Caller(t.caller.clone()))
+            .ok_or(StatusCode::UNAUTHORIZED)
+    }
+}
+
+pub async fn serve(addr: SocketAddr) {
+    // Without tokens anyone who reaches the API can manage the broker
+    if handlers::config().http_tokens.is_empty() && !addr.ip().is_loopback() {
+        panic!("The http api on {} needs --http-token, only loopback addresses can go without", addr);
+    }
+
+    let app = Router::new()
+        .route("/metrics", get(metrics))
+        .route("/clients", get(list_clients))
+        .route("/events", get(list_events))
+        .route(
+            "/events/{module}/{id}",
+            get(get_event)
+                .put(create_event)
+                .post(invoke_event)
+                .delete(delete_event),
+        );
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .unwrap_or_else(|_| panic!("Could not bind the http api to {}", addr));
+
+    info!("camelot http api initialized on {}", addr);
+
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("http api stopped: {:?}", e);
+    }
+}
+
+/// Serves only the metrics endpoint, for deployments that don't expose the admin API
+pub async fn serve_metrics(addr: SocketAddr) {
+    let app = Router::new().route("/metrics", get(metrics));
+
+    let listener = TcpListener::bind(addr)
+        .await
+        .unwrap_or_else(|_| panic!("Could not bind the metrics endpoint to {}", addr));
+
+    info!("camelot metrics initialized on {}", addr);
+
+    if let Err(e) = axum::serve(listener, app).await {
+        error!("metrics endpoint stopped: {:?}", e);
+    }
+}
+
+async fn metrics() -> impl IntoResponse {
+    (
+        [(header::CONTENT_TYPE, "text/plain; version=0.0.4")],
+        crate::metrics::render(),
+    )
+}
+
+async fn list_clients(_: Caller) -> Json<Vec<String>> {
+    let mut clients: Vec<String> = CLIENT_WRITERS.read().await.keys().cloned().collect();
+    clients.sort();
+
+    Json(clients)
+}
+
+async fn list_events(_: Caller) -> Json<Vec<EventView>> {
+    let mut events: Vec<EventView> = EVENTS
+        .read()
+        .await
+        .iter()
+        .map(|(name, event)| EventView::new(name, event))
+        .collect();
+    events.sort_by(|a, b| a.event.cmp(&b.event));
+
+    Json(events)
+}
+
+async fn get_event(
+    _: Caller,
+    Path((module, id)): Path<(String, String)>,
+) -> Result<Json<EventView>, StatusCode> {
+    let name = format!("{}:{}", module, id);
+    let events = EVENTS.read().await;
+    let event = events.get(&name).ok_or(StatusCode::NOT_FOUND)?;
+
+    Ok(Json(EventView::new(&name, event)))
+}
+
+async fn create_event(
+    Caller(caller): Caller,
+    Path((module, id)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    dispatch(ActionType::Create, &module, &id, &caller, &body).await
+}
+
+async fn invoke_event(
+    Caller(caller): Caller,
+    Path((module, id)): Path<(String, String)>,
+    body: Bytes,
+) -> Response {
+    dispatch(ActionType::Invoke, &module, &id, &caller, &body).await
+}
+
+async fn delete_event(
+    Caller(caller): Caller,
+    Path((module, id)): Path<(String, String)>,
+) -> Response {
+    dispatch(ActionType::Delete, &module, &id, &caller, &[]).await
+}
+
+/// Runs the request through the same handlers the trtcp clients go through
+async fn dispatch(
+    r#type: ActionType,
+    module: &str,
+    id: &str,
+    caller: &str,
+    body: &[u8],
+) -> Response {
+    let request = Request::new(
+        Head::new_with_version(caller),
+        Action::new(r#type, module, id),
+        body,
+    );
+    let (response, _) = handlers::handle_request(&request).await;
+    let status = response.status().r#type();
+
+    (
+        http_status(status),
+        [(STATUS_HEADER, status.name())],
+        response.body().to_vec(),
+    )
+        .into_response()
+}
+
+fn http_status(status: &StatusType) -> StatusCode {
+    match status {
+        StatusType::OK => StatusCode::OK,
+        StatusType::EventNotFound | StatusType::ListenerNotFound | StatusType::ScheduleNotFound => {
+            StatusCode::NOT_FOUND
+        }
+        StatusType::EventAlreadyExists
+        | StatusType::AlreadySubscribed
+        | StatusType::AlreadyConnected => StatusCode::CONFLICT,
+        StatusType::Unauthorized | StatusType::Forbidden => StatusCode::FORBIDDEN,
+        StatusType::Timeout => StatusCode::GATEWAY_TIMEOUT,
+        StatusType::InvalidPayload => StatusCode::UNPROCESSABLE_ENTITY,
+        StatusType::InvalidRequest | StatusType::NeedConnection => StatusCode::BAD_REQUEST,
+        StatusType::GenericError | StatusType::InternalServerError => {
+            StatusCode::INTERNAL_SERVER_ERROR
+        }
+    }
+}
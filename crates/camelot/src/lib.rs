@@ -1,14 +1,22 @@
 mod error;
+pub mod tls;
 
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
-use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
-use tokio::net::TcpStream;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
 
 pub use error::Error;
 
+/// Size of the prefix of every trtcp frame: the message type byte and the u32 length
+const FRAME_PREFIX_LEN: usize = 5;
+/// Upper bound for the length announced in a frame prefix
+const MAX_FRAME_LEN: usize = 16 * 1024 * 1024;
+
+type BoxedWriter = Box<dyn AsyncWrite + Send + Unpin>;
+type BoxedReader = Box<dyn AsyncRead + Send + Unpin>;
+
 pub struct WriteHalfClient {
     name: String,
-    stream: OwnedWriteHalf,
+    stream: BoxedWriter,
+    open: bool,
 }
 
 impl WriteHalfClient {
@@ -19,21 +27,27 @@ impl WriteHalfClient {
     }
 
     pub async fn write_slice(&mut self, message: &[u8]) -> Result<(), Error> {
-        write_stream(&mut self.stream, message).await?;
+        if let Err(e) = write_stream(&mut self.stream, message).await {
+            self.open = false;
+            return Err(e);
+        }
 
         Ok(())
     }
 
     pub async fn shutdown(&mut self) -> Result<(), Error> {
+        self.open = false;
         self.stream.shutdown().await?;
 
         Ok(())
     }
 
-    pub async fn is_open(&self) -> bool {
-        self.stream.writable().await.is_ok()
+    /// Whether the stream is still usable, that is, it hasn't been shut down and no write
+    /// into it has failed
+    pub fn is_open(&self) -> bool {
+        self.open
     }
-    
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -45,7 +59,7 @@ impl WriteHalfClient {
 
 pub struct ReadHalfClient {
     name: String,
-    stream: OwnedReadHalf,
+    stream: BoxedReader,
 }
 
 impl ReadHalfClient {
@@ -53,9 +67,7 @@ impl ReadHalfClient {
         &mut self,
         buf: &'r mut Vec<u8>,
     ) -> Result<R, Error> {
-        buf.clear();
-
-        read_stream(&mut self.stream, buf).await?;
+        self.read_frame(buf).await?;
 
         let result: Result<R, trtcp::Error> = buf.as_slice().try_into();
         match result {
@@ -64,6 +76,13 @@ impl ReadHalfClient {
         }
     }
 
+    /// Reads the raw bytes of the next frame into `buf`, without parsing them
+    pub async fn read_frame(&mut self, buf: &mut Vec<u8>) -> Result<(), Error> {
+        buf.clear();
+
+        read_stream(&mut self.stream, buf).await
+    }
+
     pub fn name(&self) -> &str {
         &self.name
     }
@@ -73,40 +92,61 @@ impl ReadHalfClient {
     }
 }
 
-pub async fn split(stream: TcpStream, name: &str) -> (ReadHalfClient, WriteHalfClient) {
-    let (read_half, write_half) = stream.into_split();
+/// Splits any bidirectional stream (TCP, TLS, Unix socket...) into the two client halves
+pub async fn split<S>(stream: S, name: &str) -> (ReadHalfClient, WriteHalfClient)
+where
+    S: AsyncRead + AsyncWrite + Send + 'static,
+{
+    let (read_half, write_half) = tokio::io::split(stream);
+    from_parts(read_half, write_half, name)
+}
+
+/// Builds the client halves from a reader and a writer that are already independent
+pub fn from_parts<R, W>(reader: R, writer: W, name: &str) -> (ReadHalfClient, WriteHalfClient)
+where
+    R: AsyncRead + Send + Unpin + 'static,
+    W: AsyncWrite + Send + Unpin + 'static,
+{
     (
         ReadHalfClient {
             name: name.to_string(),
-            stream: read_half,
+            stream: Box::new(reader),
         },
         WriteHalfClient {
             name: name.to_string(),
-            stream: write_half,
+            stream: Box::new(writer),
+            open: true,
         },
     )
 }
 
-async fn write_stream(writer: &mut OwnedWriteHalf, bytes: &[u8]) -> Result<(), Error> {
+async fn write_stream<W: AsyncWrite + Unpin>(writer: &mut W, bytes: &[u8]) -> Result<(), Error> {
     writer.write_all(bytes).await?;
     writer.flush().await?;
     Ok(())
 }
 
-async fn read_stream(reader: &mut OwnedReadHalf, buf: &mut Vec<u8>) -> Result<(), Error> {
-    loop {
-        let mut tmp_buf = [0; 1024];
-        let size = reader.read(&mut tmp_buf).await?;
+/// Reads exactly one trtcp frame, using the length of its prefix to know where it ends
+async fn read_stream<R: AsyncRead + Unpin>(reader: &mut R, buf: &mut Vec<u8>) -> Result<(), Error> {
+    let mut prefix = [0; FRAME_PREFIX_LEN];
 
-        if size == 0 {
-            return Err(Error::ConexionClosed);
-        }
+    if let Err(e) = reader.read_exact(&mut prefix).await {
+        return match e.kind() {
+            std::io::ErrorKind::UnexpectedEof => Err(Error::ConexionClosed),
+            _ => Err(e.into()),
+        };
+    }
 
-        buf.extend_from_slice(&tmp_buf[..size]);
+    let length = u32::from_be_bytes([prefix[1], prefix[2], prefix[3], prefix[4]]) as usize;
 
-        if size < 1024 {
-            break;
-        }
+    if length > MAX_FRAME_LEN {
+        return Err(Error::FrameTooLarge(length));
     }
+
+    buf.extend_from_slice(&prefix);
+    buf.resize(FRAME_PREFIX_LEN + length, 0);
+
+    reader.read_exact(&mut buf[FRAME_PREFIX_LEN..]).await?;
+
     Ok(())
 }
@@ -1,66 +1,117 @@
+mod config;
+mod delivery;
+mod filter;
 mod handlers;
-
+mod http;
+mod metrics;
+mod registry;
+mod rpc;
+mod scheduler;
+mod store;
+mod transport;
+
+use crate::config::Config;
+use crate::registry::{SystemNotice, CLIENT_CONNECTED_EVENT, CLIENT_DISCONNECTED_EVENT};
+use crate::transport::tls::CertIdentity;
 use camelot::{Error, ReadHalfClient, WriteHalfClient};
+use clap::Parser;
 use std::collections::HashMap;
 use std::sync::{Arc, LazyLock};
-use tokio::net::TcpStream;
+use std::time::Duration;
 use tokio::sync::{Mutex, RwLock};
-use tracing::{error, info};
-use trtcp::{ActionType, Head, Request, Response, Status, StatusType};
+use tokio::task::JoinSet;
+use tracing::{error, info, warn};
+use trtcp::{Action, ActionType, Head, Request, Response, Status, StatusType};
+
+type ClientWriters = Arc<RwLock<HashMap<String, Arc<Mutex<WriteHalfClient>>>>>;
 
-static CLIENT_WRITERS: LazyLock<Arc<RwLock<HashMap<String, Arc<Mutex<WriteHalfClient>>>>>> =
+static CLIENT_WRITERS: LazyLock<ClientWriters> =
     LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
 
 #[tokio::main]
 async fn main() {
     tracing_subscriber::fmt::init();
-    let args: Vec<String> = std::env::args().collect();
-
-    let port = if args.len() == 2 {
-        args[1].parse().expect("Invalid port")
-    } else {
-        1237
-    };
 
-    start_server(port).await;
+    start_server(Config::parse()).await;
 }
 
-pub async fn start_server(port: u16) {
-    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
+pub async fn start_server(config: Config) {
+    metrics::init();
+    handlers::init(&config);
+
+    store::init(&config).expect("Could not open the event store");
+    let dead_letter_retention = config.dead_letter_retention.min(config.max_retention);
+    let loaded = registry::load(dead_letter_retention)
         .await
-        .unwrap_or_else(|_| panic!("Could not bind to port {}", port));
+        .expect("Could not load the events of the store");
+    info!("{} events loaded from the store", loaded);
+    let schedules = scheduler::load().await.expect("Could not load the schedules of the store");
+    info!("{} schedules loaded from the store", schedules);
 
-    info!("camelot initialized on port {}", port);
+    let mut listeners = JoinSet::new();
 
-    loop {
-        match listener.accept().await {
-            Ok((socket, _)) => {
-                tokio::spawn(async move {
-                    handle_client(socket).await;
-                });
+    listeners.spawn(delivery::redeliver(
+        Duration::from_millis(config.ack_timeout_ms),
+        config.max_attempts,
+    ));
+    listeners.spawn(scheduler::run());
+
+    if !config.no_tcp {
+        listeners.spawn(transport::tcp::serve(config.clone()));
+    }
+
+    if let Some(addr) = config.ws {
+        listeners.spawn(transport::ws::serve(addr, config.clone()));
+    }
+
+    if let Some(addr) = config.json {
+        listeners.spawn(transport::json::serve(addr));
+    }
+
+    if let Some(addr) = config.http {
+        listeners.spawn(http::serve(addr));
+    }
+
+    if let Some(addr) = config.metrics {
+        listeners.spawn(http::serve_metrics(addr));
+    }
+
+    if let Some(path) = config.unix.clone() {
+        #[cfg(unix)]
+        listeners.spawn(transport::unix::serve(path, config.unix_mode));
+        #[cfg(not(unix))]
+        error!("unix sockets aren't supported on this platform ({:?})", path);
+    }
+
+    while let Some(result) = listeners.join_next().await {
+        if let Err(e) = result {
+            if e.is_panic() {
+                std::panic::resume_unwind(e.into_panic());
             }
-            Err(e) => error!("couldn't get client connection: {:?}", e),
         }
     }
 }
 
-async fn handle_client(socket: TcpStream) {
-    let client_addr = socket.peer_addr();
-    info!(
-        "new tcp connection established with client {:?}",
-        client_addr
-    );
+async fn handle_client(
+    reader: ReadHalfClient,
+    writer: WriteHalfClient,
+    client_addr: String,
+    identity: Option<CertIdentity>,
+) {
+    info!("new connection established with client {}", client_addr);
+
+    let first_connection = handle_first_connection(reader, writer, &client_addr, identity).await;
 
-    let (mut reader, client_name) = match handle_first_connection(socket).await {
+    let (mut reader, client_name, mut last_will) = match first_connection {
         Ok(o) => {
-            let (reader, mut writer, caller_name) = match o {
+            let (reader, mut writer, caller_name, last_will) = match o {
                 Some(client) => client,
                 None => return,
             };
 
             {
                 let writers = CLIENT_WRITERS.read().await;
-                if writers.contains_key(&caller_name) && writers.get(&caller_name).unwrap().lock().await.is_open().await {
+                if writers.contains_key(&caller_name) && writers.get(&caller_name).unwrap().lock().await.is_open() {
                     info!(
                         "disconnecting client that used a name that is already in use ({})",
                         caller_name
@@ -82,17 +133,33 @@ async fn handle_client(socket: TcpStream) {
 
                 let response = Response::new_ok(&caller_name);
                 let _ = writer.write(response).await;
-                
-                writers.insert(caller_name.to_string(), Arc::new(Mutex::new(writer)));
+
+                // The writer stays locked until the queued callbacks are replayed, so the
+                // new ones are written after them
+                let writer = Arc::new(Mutex::new(writer));
+                let mut writer_guard = writer.clone().lock_owned().await;
+
+                writers.insert(caller_name.to_string(), writer);
+                metrics::CONNECTED_CLIENTS.inc();
+                drop(writers);
+
+                replay_offline(&mut writer_guard, &caller_name).await;
             }
 
-            (reader, caller_name)
+            let notice = SystemNotice {
+                caller: &caller_name,
+                reason: "connected",
+                event: None,
+            };
+            registry::publish(CLIENT_CONNECTED_EVENT, &notice).await;
+
+            (reader, caller_name, last_will)
         }
         Err(e) => {
             if let Error::ConexionClosed = e { 
-                info!("connection closed with {:?}", client_addr)
+                info!("connection closed with {}", client_addr)
             } else { 
-                error!("error handling first client connection ({:?}) {:?}", client_addr, e)
+                error!("error handling first client connection ({}) {:?}", client_addr, e)
             }
             return;
         }
@@ -100,99 +167,284 @@ async fn handle_client(socket: TcpStream) {
 
     let mut buffer = vec![];
     info!(
-        "persistent connection established with client {:?}",
+        "persistent connection established with client {}",
         client_addr
     );
 
     loop {
-        let request: Result<Request, Error> = reader.read(&mut buffer).await;
+        let read = reader.read_frame(&mut buffer);
+        let read = match handlers::config().idle_timeout_ms {
+            Some(ms) => tokio::time::timeout(Duration::from_millis(ms), read)
+                .await
+                .unwrap_or(Err(Error::Timeout)),
+            None => read.await,
+        };
+        let request = read.and_then(|_| Ok(Request::try_from(buffer.as_slice())?));
 
+        // The connection name is the only identity a client can act as
         let request = match request {
-            Ok(request) => request,
-            Err(_) => {
-                // Sending the shutdown signal to the client
-                {
-                    let _ = CLIENT_WRITERS
-                        .write()
-                        .await
-                        .get(&client_name)
-                        .expect("Client not found")
-                        .lock()
-                        .await
-                        .shutdown()
-                        .await;
-                }
-
-                // Removing the client from the list
-                {
-                    CLIENT_WRITERS.write().await.remove(&client_name);
-                }
+            Ok(request) => request.with_caller(&client_name),
+            Err(e) => {
+                remove_client(&client_name).await;
                 info!(
-                    "due to an error while reading the client ({:?}) request, this has been disconnected and removed",
+                    "due to an error while reading the client ({}) request, this has been disconnected and removed",
                     client_addr
                 );
+
+                let reason = match e {
+                    Error::ConexionClosed => "closed".to_string(),
+                    Error::Timeout => "timeout".to_string(),
+                    e => e.to_string(),
+                };
+                client_disconnected(&client_name, &reason).await;
+                if let Some(will) = last_will {
+                    invoke_last_will(&client_name, will).await;
+                }
                 return;
             }
         };
 
+        let request = without_broker_headers(request);
+
+        // A clean disconnect, the last will is discarded
+        if *request.action().r#type() == ActionType::Disconnect {
+            {
+                let guard = CLIENT_WRITERS.read().await;
+                let mut writer = guard.get(&client_name).expect("Client not found").lock().await;
+                let _ = writer.write(Response::new_ok(&client_name)).await;
+            }
+
+            remove_client(&client_name).await;
+            info!("client {} disconnected", client_addr);
+            client_disconnected(&client_name, "disconnected").await;
+            return;
+        }
+
+        metrics::BYTES_IN.inc_by(buffer.len() as u64);
+
         // Creating a response
-        let response = handlers::handle_request(&request).await;
+        let (response, follow_up) = handlers::handle_request(&request).await;
+        let response: Vec<u8> = response.into();
 
         {
-            let guard = CLIENT_WRITERS.read().await;
-            let mut writer = guard
-                .get(&client_name)
-                .expect("Client not found")
-                .lock()
-                .await;
-
-            if writer.write(response).await.is_err() {
-                error!("Error writing response to client {:?}", client_addr);
+            let mut writer = match follow_up.writer {
+                Some(writer) => writer,
+                None => {
+                    let writer = CLIENT_WRITERS.read().await.get(&client_name).cloned();
+                    writer.expect("Client not found").lock_owned().await
+                }
+            };
+
+            let mut written = Ok(());
+            for frame in std::iter::once(&response).chain(follow_up.frames.iter()) {
+                written = writer.write_slice(frame).await;
+                if written.is_err() {
+                    break;
+                }
+                metrics::BYTES_OUT.inc_by(frame.len() as u64);
+            }
+
+            if written.is_err() {
+                error!("Error writing response to client {}", client_addr);
                 let _ = writer.shutdown().await;
                 drop(writer);
-                drop(guard);
                 CLIENT_WRITERS.write().await.remove(&client_name);
+                metrics::CONNECTED_CLIENTS.dec();
+                client_disconnected(&client_name, "Error writing into the stream").await;
+                if let Some(will) = last_will.take() {
+                    invoke_last_will(&client_name, will).await;
+                }
                 break;
             }
         }
     }
 }
 
-async fn handle_first_connection(
-    socket: TcpStream,
-) -> Result<Option<(ReadHalfClient, WriteHalfClient, String)>, Error> {
-    let client_addr = socket.peer_addr();
-    let (mut reader, mut writer) = camelot::split(socket, "tmp").await;
+/// Sends the shutdown signal to the client and removes it from the connected ones
+async fn remove_client(client_name: &str) {
+    {
+        let _ = CLIENT_WRITERS
+            .write()
+            .await
+            .get(client_name)
+            .expect("Client not found")
+            .lock()
+            .await
+            .shutdown()
+            .await;
+    }
+
+    CLIENT_WRITERS.write().await.remove(client_name);
+    metrics::CONNECTED_CLIENTS.dec();
+}
+
+/// Only the scheduler marks invokes as scheduled, clients can't forge it
+fn without_broker_headers(request: Request<'_>) -> Request<'_> {
+    match request.action().r#type() {
+        ActionType::Invoke => request.without_header(scheduler::SCHEDULE_ID_HEADER),
+        _ => request,
+    }
+}
+
+/// Event a client asks the broker to invoke for it if its connection ends abnormally
+struct LastWill {
+    module: String,
+    id: String,
+    body: Vec<u8>,
+}
+
+/// Invokes the last will through the normal invoke path, on behalf of the client
+async fn invoke_last_will(client_name: &str, will: LastWill) {
+    info!("invoking the last will of {} on {}:{}", client_name, will.module, will.id);
+
+    let request = Request::new(
+        Head::new_with_version(client_name),
+        Action::new(ActionType::Invoke, &will.module, &will.id),
+        will.body.as_slice(),
+    );
+    let (response, _) = handlers::handle_request(&request).await;
+
+    if *response.status().r#type() != StatusType::OK {
+        warn!(
+            "the last will of {} failed with status {}",
+            client_name,
+            response.status().r#type().name()
+        );
+    }
+}
+
+async fn client_disconnected(client_name: &str, reason: &str) {
+    let notice = SystemNotice {
+        caller: client_name,
+        reason,
+        event: None,
+    };
+    registry::publish(CLIENT_DISCONNECTED_EVENT, &notice).await;
+}
+
+/// Writes the callbacks queued while the client was offline, oldest first
+async fn replay_offline(writer: &mut WriteHalfClient, client_name: &str) {
+    let frames = match store::offline_frames(client_name) {
+        Ok(frames) => frames,
+        Err(e) => {
+            error!("could not read the callbacks queued for {}: {}", client_name, e);
+            return;
+        }
+    };
+
+    let mut last_delivered = None;
+    let mut replayed = 0;
+    for (id, frame) in frames.iter() {
+        if let Err(e) = writer.write_slice(frame).await {
+            error!("could not replay the callbacks queued for {}: {}", client_name, e);
+            break;
+        }
+
+        last_delivered = Some(*id);
+        replayed += 1;
+        metrics::CALLBACKS_REPLAYED.inc();
+        metrics::BYTES_OUT.inc_by(frame.len() as u64);
+    }
+
+    if let Some(last) = last_delivered {
+        info!("{} queued callbacks replayed to {}", replayed, client_name);
+        if let Err(e) = store::store().remove_pending(client_name, last) {
+            error!("could not remove the callbacks replayed to {}: {}", client_name, e);
+        }
+    }
+}
 
-    info!("handling first connection of {:?}", client_addr);
+async fn handle_first_connection(
+    mut reader: ReadHalfClient,
+    mut writer: WriteHalfClient,
+    client_addr: &str,
+    identity: Option<CertIdentity>,
+) -> Result<Option<(ReadHalfClient, WriteHalfClient, String, Option<LastWill>)>, Error> {
+    info!("handling first connection of {}", client_addr);
     
     let mut buff = Vec::new();
-    let request: Request = reader.read(&mut buff).await?;
+    reader.read_frame(&mut buff).await?;
+    metrics::BYTES_IN.inc_by(buff.len() as u64);
+    let request = Request::try_from(buff.as_slice())?;
+
+    let client_name = match &identity {
+        Some(identity) => match identity.resolve(request.head().caller()) {
+            Some(name) => name.to_string(),
+            None => {
+                info!("caller name doesn't match the certificate of {}", client_addr);
+                let response = Response::new(
+                    Head::new_with_version(request.head().caller()),
+                    Status::new(StatusType::Unauthorized),
+                    "Caller name doesn't match the client certificate".as_bytes(),
+                );
 
-    let client_name = request.head().caller();
+                writer.write(response).await?;
+                writer.shutdown().await?;
+                return Ok(None);
+            }
+        },
+        None => request.head().caller().to_string(),
+    };
+
+    // Listeners tell the notices of the broker apart by this caller name
+    if client_name == registry::SYSTEM_CALLER {
+        info!("reserved caller name sended by {}", client_addr);
+        let response = Response::new(
+            Head::new_with_version(&client_name),
+            Status::new(StatusType::Forbidden),
+            "The caller name is reserved for the broker".as_bytes(),
+        );
+
+        writer.write(response).await?;
+        writer.shutdown().await?;
+        return Ok(None);
+    }
 
     match request.action().r#type() {
         ActionType::Connect => {
-            info!("persistence connection request sended by {:?}", client_addr);
+            info!("persistence connection request sended by {}", client_addr);
+
+            let last_will = match request.head().header("will") {
+                None => None,
+                Some(event) => match event.split_once(':') {
+                    Some((module, id)) if !module.is_empty() && !id.is_empty() => Some(LastWill {
+                        module: module.to_string(),
+                        id: id.to_string(),
+                        body: request.body().to_vec(),
+                    }),
+                    _ => {
+                        let response = Response::new(
+                            Head::new_with_version(&client_name),
+                            Status::new(StatusType::InvalidRequest),
+                            "The will must be an event as module:id".as_bytes(),
+                        );
+
+                        writer.write(response).await?;
+                        writer.shutdown().await?;
+                        return Ok(None);
+                    }
+                },
+            };
 
-            writer.set_name(client_name.to_string());
-            reader.set_name(client_name.to_string());
+            writer.set_name(client_name.clone());
+            reader.set_name(client_name.clone());
             
-            Ok(Some((reader, writer, client_name.to_string())))
+            Ok(Some((reader, writer, client_name, last_will)))
         }
         ActionType::Invoke => {
-            info!("temporal connection request (invoke) sended by {:?}", client_addr);
-            let response = handlers::handle_request(&request).await;
+            info!("temporal connection request (invoke) sended by {}", client_addr);
+            let request = without_broker_headers(request.with_caller(&client_name));
+            let (response, _) = handlers::handle_request(&request).await;
             writer.write(response).await?;
             writer.shutdown().await?;
             Ok(None)
         }
         _ => {
-            info!("invalid request for a temporal connection sended by {:?}", client_addr);
+            info!("invalid request for a temporal connection sended by {}", client_addr);
             let response = Response::new(
-                Head::new_with_version(client_name),
+                Head::new_with_version(&client_name),
                 Status::new(StatusType::NeedConnection),
-                "".as_ref(),
+                "".as_bytes(),
             );
 
             writer.write(response).await?;
@@ -205,13 +457,13 @@ async fn handle_first_connection(
 #[cfg(test)]
 mod test {
     use super::*;
-    use std::time::Duration;
+    use tokio::net::TcpStream;
     use trtcp::Head;
 
     #[tokio::test]
     async fn test_handle_clients() {
         let server = tokio::spawn(async move {
-            start_server(1237).await;
+            start_server(Config::parse_from(["camelot", "1237"])).await;
         });
 
         for i in 0..10 {
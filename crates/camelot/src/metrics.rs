@@ -0,0 +1,141 @@
+use prometheus::{
+    register_histogram, register_int_counter, register_int_counter_vec, register_int_gauge,
+    Encoder, Histogram, IntCounter, IntCounterVec, IntGauge, TextEncoder,
+};
+use std::sync::LazyLock;
+
+pub static CONNECTIONS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "camelot_connections_total",
+        "Connections accepted by each listener",
+        &["transport"]
+    )
+    .unwrap()
+});
+
+pub static CONNECTED_CLIENTS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "camelot_connected_clients",
+        "Clients with a persistent connection"
+    )
+    .unwrap()
+});
+
+pub static REQUESTS: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "camelot_requests_total",
+        "Requests handled by action type and response status",
+        &["action", "status"]
+    )
+    .unwrap()
+});
+
+pub static CALLBACKS_SENT: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "camelot_callbacks_sent_total",
+        "Callbacks written to the listeners"
+    )
+    .unwrap()
+});
+
+pub static CALLBACKS_FAILED: LazyLock<IntCounterVec> = LazyLock::new(|| {
+    register_int_counter_vec!(
+        "camelot_callbacks_failed_total",
+        "Callbacks that couldn't be delivered",
+        &["reason"]
+    )
+    .unwrap()
+});
+
+pub static CALLBACKS_QUEUED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "camelot_callbacks_queued_total",
+        "Callbacks kept for offline durable listeners"
+    )
+    .unwrap()
+});
+
+pub static CALLBACKS_REPLAYED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "camelot_callbacks_replayed_total",
+        "Queued callbacks written to listeners after they reconnected"
+    )
+    .unwrap()
+});
+
+pub static CALLBACKS_REDELIVERED: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "camelot_callbacks_redelivered_total",
+        "Callbacks sent again because they weren't acknowledged in time"
+    )
+    .unwrap()
+});
+
+pub static PENDING_ACKS: LazyLock<IntGauge> = LazyLock::new(|| {
+    register_int_gauge!(
+        "camelot_pending_acks",
+        "Callbacks waiting to be acknowledged"
+    )
+    .unwrap()
+});
+
+pub static DEAD_LETTERS: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!(
+        "camelot_dead_letters_total",
+        "Callbacks moved to the dead-letter event"
+    )
+    .unwrap()
+});
+
+pub static BYTES_IN: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("camelot_bytes_in_total", "Bytes of the frames read").unwrap()
+});
+
+pub static BYTES_OUT: LazyLock<IntCounter> = LazyLock::new(|| {
+    register_int_counter!("camelot_bytes_out_total", "Bytes of the frames written").unwrap()
+});
+
+pub static FAN_OUT: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "camelot_invoke_fan_out",
+        "Listeners of the event on each invoke",
+        vec![0.0, 1.0, 2.0, 5.0, 10.0, 25.0, 50.0, 100.0, 250.0]
+    )
+    .unwrap()
+});
+
+pub static DELIVERY_LATENCY: LazyLock<Histogram> = LazyLock::new(|| {
+    register_histogram!(
+        "camelot_invoke_delivery_seconds",
+        "Time from the invoke being received until each callback is written"
+    )
+    .unwrap()
+});
+
+/// Every metric in the prometheus text format
+pub fn render() -> String {
+    let mut buffer = Vec::new();
+    TextEncoder::new()
+        .encode(&prometheus::gather(), &mut buffer)
+        .expect("Could not encode the metrics");
+
+    String::from_utf8(buffer).expect("Metrics aren't valid utf-8")
+}
+
+/// Registers every metric, so all of them are exported before their first update
+pub fn init() {
+    LazyLock::force(&CONNECTIONS);
+    LazyLock::force(&CONNECTED_CLIENTS);
+    LazyLock::force(&REQUESTS);
+    LazyLock::force(&CALLBACKS_SENT);
+    LazyLock::force(&CALLBACKS_FAILED);
+    LazyLock::force(&CALLBACKS_QUEUED);
+    LazyLock::force(&CALLBACKS_REPLAYED);
+    LazyLock::force(&CALLBACKS_REDELIVERED);
+    LazyLock::force(&PENDING_ACKS);
+    LazyLock::force(&DEAD_LETTERS);
+    LazyLock::force(&BYTES_IN);
+    LazyLock::force(&BYTES_OUT);
+    LazyLock::force(&FAN_OUT);
+    LazyLock::force(&DELIVERY_LATENCY);
+}
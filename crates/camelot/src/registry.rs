@@ -0,0 +1,475 @@
+use crate::delivery::{self, Callback, DEAD_LETTER_EVENT};
+use crate::filter::Filter;
+use crate::store;
+use jsonschema::Validator;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::collections::{HashMap, VecDeque};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio::sync::RwLock;
+use tracing::error;
+
+/// Header with the sequence number of the invoke on every callback
+pub const SEQ_HEADER: &str = "seq";
+
+/// Sequence numbers saved in the store at once
+const SEQ_BLOCK: u64 = 1000;
+
+/// Caller of the requests made by the broker itself
+pub const SYSTEM_CALLER: &str = "camelot";
+
+/// Published when a client connects
+pub const CLIENT_CONNECTED_EVENT: &str = "camelot:clientConnected";
+/// Published when the connection of a client ends, cleanly or not
+pub const CLIENT_DISCONNECTED_EVENT: &str = "camelot:clientDisconnected";
+pub const EVENT_CREATED_EVENT: &str = "camelot:eventCreated";
+pub const EVENT_DELETED_EVENT: &str = "camelot:eventDeleted";
+
+/// Events the broker creates on startup, they aren't persisted and only the broker
+/// invokes them
+const SYSTEM_EVENTS: [&str; 5] = [
+    DEAD_LETTER_EVENT,
+    CLIENT_CONNECTED_EVENT,
+    CLIENT_DISCONNECTED_EVENT,
+    EVENT_CREATED_EVENT,
+    EVENT_DELETED_EVENT,
+];
+
+/// Body of the presence and registry system events
+#[derive(Serialize)]
+pub struct SystemNotice<'a> {
+    /// Client the notice is about, or the one that created or deleted the event
+    pub caller: &'a str,
+    pub reason: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub event: Option<&'a str>,
+}
+
+type EventRegistry = Arc<RwLock<HashMap<String, Event>>>;
+
+pub static EVENTS: LazyLock<EventRegistry> =
+    LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+type PatternRegistry = Arc<RwLock<HashMap<String, Vec<Listener>>>>;
+
+/// Listeners of every pattern subscription. They are copied into the listeners of the
+/// matching events when they subscribe and when an event is created, so invokes never look
+/// at them. Always locked after `EVENTS`
+pub static PATTERNS: LazyLock<PatternRegistry> =
+    LazyLock::new(|| Arc::new(RwLock::new(HashMap::new())));
+
+/// Data of an event that is persisted by the store
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct EventMetadata {
+    pub creator: String,
+    pub created_at: u64,
+    /// The last invoke is kept and sent to every new listener
+    #[serde(default)]
+    pub retained: bool,
+    /// Number of invokes kept for history requests, none when 0
+    #[serde(default)]
+    pub retention: usize,
+    /// JSON Schema the bodies of the invokes have to match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub schema: Option<Value>,
+    /// Sequence numbers up to this one may have been used, the broker goes on after it
+    #[serde(default)]
+    pub last_seq: u64,
+}
+
+#[derive(Debug)]
+pub struct Event {
+    pub metadata: EventMetadata,
+    pub listeners: Vec<Listener>,
+    /// Last invoke of a retained event, it only lives in memory
+    pub retained_value: Mutex<Option<RetainedValue>>,
+    /// The last `retention` invokes, oldest first. It only lives in memory
+    pub history: Mutex<VecDeque<HistoryEntry>>,
+    /// Sequence number of the last invoke
+    last_seq: AtomicU64,
+    /// Highest sequence number saved in the store. They are reserved in blocks, so most
+    /// invokes don't write to it
+    reserved_seq: AtomicU64,
+    /// Held from the sequence number of an invoke to the end of its fan-out, so the
+    /// callbacks are written in sequence order
+    pub sequencer: tokio::sync::Mutex<()>,
+    /// Round robin counter of the responders
+    next_responder: AtomicUsize,
+    /// Round robin counters of the consumer groups
+    group_turns: Mutex<HashMap<String, usize>>,
+    /// Compiled `metadata.schema`
+    validator: Option<Validator>,
+}
+
+#[derive(Clone, Debug)]
+pub struct RetainedValue {
+    pub callback: Callback,
+    /// Headers of the invoke, for the filters of the new listeners
+    pub headers: Vec<(String, String)>,
+}
+
+/// First part of an invoke body that doesn't match the schema of the event
+#[derive(Serialize, Debug)]
+pub struct SchemaViolation {
+    /// JSON pointer to the failing value, empty for the whole body
+    pub path: String,
+    pub error: String,
+}
+
+/// An invoke kept by the retention of the event
+#[derive(Debug, Clone)]
+pub struct HistoryEntry {
+    pub seq: u64,
+    /// Unix time in milliseconds
+    pub timestamp: u64,
+    pub callback: Callback,
+}
+
+impl Event {
+    pub fn new(creator: &str) -> Self {
+        Self::with_metadata(EventMetadata {
+            creator: creator.to_string(),
+            created_at: unix_time(),
+            retained: false,
+            retention: 0,
+            schema: None,
+            last_seq: 0,
+        })
+    }
+
+    pub fn with_metadata(metadata: EventMetadata) -> Self {
+        let validator = metadata.schema.as_ref().and_then(|schema| {
+            jsonschema::validator_for(schema)
+                .inspect_err(|e| error!("invalid schema of an event created by {}: {}", metadata.creator, e))
+                .ok()
+        });
+        let last_seq = metadata.last_seq;
+
+        Self {
+            metadata,
+            listeners: Vec::new(),
+            retained_value: Mutex::new(None),
+            history: Mutex::new(VecDeque::new()),
+            last_seq: AtomicU64::new(last_seq),
+            reserved_seq: AtomicU64::new(last_seq),
+            sequencer: tokio::sync::Mutex::new(()),
+            next_responder: AtomicUsize::new(0),
+            group_turns: Mutex::new(HashMap::new()),
+            validator,
+        }
+    }
+
+    /// Sets the schema of the bodies of the invokes, returns why when it isn't valid
+    pub fn set_schema(&mut self, schema: Value) -> Result<(), String> {
+        self.validator = Some(jsonschema::validator_for(&schema).map_err(|e| e.to_string())?);
+        self.metadata.schema = Some(schema);
+
+        Ok(())
+    }
+
+    /// Checks the body of an invoke against the schema of the event, if it has one
+    pub fn validate(&self, body: &[u8]) -> Result<(), SchemaViolation> {
+        let Some(validator) = &self.validator else {
+            return Ok(());
+        };
+
+        let body: Value = serde_json::from_slice(body).map_err(|e| SchemaViolation {
+            path: String::new(),
+            error: format!("The body isn't JSON: {}", e),
+        })?;
+
+        validator.validate(&body).map_err(|e| SchemaViolation {
+            path: e.instance_path.to_string(),
+            error: e.to_string(),
+        })
+    }
+
+    /// Assigns the next sequence number to an invoke, stamps it on the callback and keeps
+    /// it when the event has retention and `keep` is set. Hold `sequencer` until the
+    /// callback is delivered
+    pub fn record(&self, event_name: &str, callback: &mut Callback, keep: bool) -> u64 {
+        let seq = self.last_seq.fetch_add(1, Ordering::Relaxed) + 1;
+
+        // The numbers go on after a restart, past the last block that was reserved
+        if seq > self.reserved_seq.load(Ordering::Relaxed) {
+            let reserved = seq + SEQ_BLOCK - 1;
+            let metadata = EventMetadata {
+                last_seq: reserved,
+                ..self.metadata.clone()
+            };
+
+            match store::store().save_event(event_name, &metadata) {
+                Ok(()) => self.reserved_seq.store(reserved, Ordering::Relaxed),
+                Err(e) => error!("could not reserve the sequence numbers of {}: {}", event_name, e),
+            }
+        }
+
+        if keep && self.metadata.retention > 0 {
+            let mut history = self.history.lock().unwrap();
+            history.push_back(HistoryEntry {
+                seq,
+                timestamp: unix_time_millis(),
+                callback: callback.clone(),
+            });
+
+            while history.len() > self.metadata.retention {
+                history.pop_front();
+            }
+        }
+
+        callback.headers.push((SEQ_HEADER.to_string(), seq.to_string()));
+        seq
+    }
+
+    pub fn listener_position(&self, name: &str) -> Option<usize> {
+        self.listeners.iter().position(|l| l.name == name)
+    }
+
+    /// Picks one of the responders of the event among the connected clients, taking turns
+    pub fn next_responder(&self, connected: impl Fn(&str) -> bool) -> Option<&Listener> {
+        let responders: Vec<&Listener> = self
+            .listeners
+            .iter()
+            .filter(|l| l.responder && connected(&l.name))
+            .collect();
+
+        if responders.is_empty() {
+            return None;
+        }
+
+        let turn = self.next_responder.fetch_add(1, Ordering::Relaxed);
+        Some(responders[turn % responders.len()])
+    }
+
+    /// Takes the next turn of a consumer group among `members` listeners
+    pub fn next_group_turn(&self, group: &str, members: usize) -> usize {
+        let mut turns = self.group_turns.lock().unwrap();
+        let turn = turns.entry(group.to_string()).or_default();
+        let current = *turn;
+        *turn = current.wrapping_add(1);
+
+        current % members
+    }
+
+    /// Adds the listener of a pattern, unless the client already listens to the event
+    pub fn add_pattern_listener(&mut self, listener: &Listener) {
+        if self.listener_position(&listener.name).is_none() {
+            self.listeners.push(listener.clone());
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+pub struct Listener {
+    pub name: String,
+    /// Durable subscriptions are persisted by the store
+    #[serde(default)]
+    pub durable: bool,
+    /// Callbacks have to be acknowledged, the ones that aren't are sent again
+    #[serde(default)]
+    pub ack: bool,
+    /// Receives the requests of the event instead of its invokes
+    #[serde(default)]
+    pub responder: bool,
+    /// Consumer group, each invoke goes to only one of its members
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    /// Only the invokes that pass it are delivered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<Filter>,
+    /// Pattern the subscription comes from, when it isn't to the event itself
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pattern: Option<String>,
+}
+
+/// Patterns have `*` in the module or the id of the event, matching any text
+pub fn is_pattern(event_name: &str) -> bool {
+    event_name.contains('*')
+}
+
+/// Whether the event matches the pattern. The module and the id are matched separately,
+/// so `*` never matches the `:` between them
+pub fn matches(pattern: &str, event_name: &str) -> bool {
+    match (pattern.split_once(':'), event_name.split_once(':')) {
+        (Some((pattern_module, pattern_id)), Some((module, id))) => {
+            glob(pattern_module, module) && glob(pattern_id, id)
+        }
+        _ => false,
+    }
+}
+
+fn glob(pattern: &str, text: &str) -> bool {
+    let mut parts = pattern.split('*');
+    let first = parts.next().unwrap_or_default();
+
+    let Some(mut rest) = text.strip_prefix(first) else {
+        return false;
+    };
+
+    let parts: Vec<&str> = parts.collect();
+    let Some((last, middle)) = parts.split_last() else {
+        // No wildcard at all
+        return rest.is_empty();
+    };
+
+    for part in middle {
+        match rest.find(part) {
+            Some(i) => rest = &rest[i + part.len()..],
+            None => return false,
+        }
+    }
+
+    rest.len() >= last.len() && rest.ends_with(last)
+}
+
+/// Adds the listeners of the patterns that match to a new event
+pub async fn apply_patterns(event_name: &str, event: &mut Event) {
+    let patterns = PATTERNS.read().await;
+
+    for (pattern, listeners) in patterns.iter() {
+        if matches(pattern, event_name) {
+            for listener in listeners {
+                event.add_pattern_listener(listener);
+            }
+        }
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
+
+pub fn unix_time_millis() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis() as u64)
+        .unwrap_or_default()
+}
+
+/// Fills the registry with the events and the durable subscriptions of the store,
+/// returns the number of events read from the store. The dead-letter event keeps the
+/// last `dead_letter_retention` letters, so they can be read when nobody was listening
+pub async fn load(dead_letter_retention: usize) -> Result<usize, store::Error> {
+    let store = store::store();
+    let mut events = EVENTS.write().await;
+
+    for name in SYSTEM_EVENTS {
+        events.insert(name.to_string(), Event::new(SYSTEM_CALLER));
+    }
+
+    let stored = store.load_events()?;
+    // System events are only there for their sequence numbers
+    let loaded = stored.iter().filter(|(name, _)| !is_system_event(name)).count();
+    for (name, metadata) in stored {
+        events.insert(name, Event::with_metadata(metadata));
+    }
+    if let Some(dead_letters) = events.get_mut(DEAD_LETTER_EVENT) {
+        dead_letters.metadata.retention = dead_letter_retention;
+    }
+
+    let mut patterns = PATTERNS.write().await;
+    for (event, listener) in store.load_subscriptions()? {
+        if is_pattern(&event) {
+            patterns.entry(event).or_default().push(listener);
+        } else if let Some(event) = events.get_mut(&event) {
+            event.listeners.push(listener);
+        }
+    }
+
+    for (pattern, listeners) in patterns.iter() {
+        for (name, event) in events.iter_mut() {
+            if matches(pattern, name) {
+                listeners.iter().for_each(|l| event.add_pattern_listener(l));
+            }
+        }
+    }
+
+    Ok(loaded)
+}
+
+/// Removes an event and its listeners from the registry, returns whether it existed.
+/// The listeners get a callback with the `deleted` header
+pub async fn delete_event(event_name: &str, deleted_by: &str) -> Result<bool, store::Error> {
+    let event = {
+        let mut events = EVENTS.write().await;
+
+        if !events.contains_key(event_name) {
+            return Ok(false);
+        }
+
+        store::store().delete_event(event_name)?;
+        events.remove(event_name).unwrap()
+    };
+    delivery::purge(event_name, None).await;
+
+    let (module, id) = event_name.split_once(':').unwrap_or((event_name, ""));
+    let mut callback = Callback::new(SYSTEM_CALLER, module, id, &[]);
+    callback.headers.push(("deleted".to_string(), "true".to_string()));
+
+    // There is nothing left to acknowledge
+    let listeners: Vec<Listener> = event
+        .listeners
+        .into_iter()
+        .map(|l| Listener { ack: false, ..l })
+        .collect();
+    delivery::fan_out(&listeners, &callback, Instant::now()).await;
+
+    let notice = SystemNotice {
+        caller: deleted_by,
+        reason: "deleted",
+        event: Some(event_name),
+    };
+    publish(EVENT_DELETED_EVENT, &notice).await;
+
+    Ok(true)
+}
+
+/// Invokes a system event on behalf of the broker, with the body serialized as JSON.
+/// `EVENTS` can't be locked by the caller
+pub async fn publish(event_name: &str, body: &impl Serialize) {
+    let body = serde_json::to_vec(body).expect("Could not serialize the system event");
+    let (module, id) = event_name.split_once(':').unwrap_or((event_name, ""));
+    let mut callback = Callback::new(SYSTEM_CALLER, module, id, &body);
+
+    let events = EVENTS.read().await;
+    let Some(event) = events.get(event_name) else {
+        return;
+    };
+
+    let _sequence = event.sequencer.lock().await;
+    event.record(event_name, &mut callback, true);
+    // Most of them have no listeners, they don't count as invokes in the metrics
+    if !event.listeners.is_empty() {
+        delivery::fan_out(&event.listeners, &callback, Instant::now()).await;
+    }
+}
+
+pub fn is_system_event(event_name: &str) -> bool {
+    SYSTEM_EVENTS.contains(&event_name)
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_pattern_matches() {
+        assert!(matches("cash-register:*", "cash-register:orderModified"));
+        assert!(matches("*:orderModified", "kitchen:orderModified"));
+        assert!(matches("*:*", "kitchen:orderModified"));
+        assert!(matches("cash-*:order*", "cash-register:orderModified"));
+        assert!(matches("*:*Modified", "kitchen:orderModified"));
+        assert!(matches("cash-register:orderModified", "cash-register:orderModified"));
+
+        assert!(!matches("cash-register:*", "kitchen:orderModified"));
+        assert!(!matches("*:orderModified", "kitchen:orderCreated"));
+        assert!(!matches("*", "kitchen:orderModified"));
+        assert!(!matches("*:order*d", "kitchen:orderModifiedLater"));
+        assert!(!matches("kitchen*", "kitchen:orderModified"));
+    }
+}
@@ -0,0 +1,66 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use tokio::sync::{mpsc, Mutex};
+
+/// Header that links the callbacks expecting a reply with their replies
+pub const REQUEST_ID_HEADER: &str = "request-id";
+
+/// A reply to a callback, tagged with the client that sent it
+#[derive(Debug)]
+pub struct Reply {
+    pub caller: String,
+    pub body: Vec<u8>,
+}
+
+/// Callback waiting for the replies of some clients, each one can reply once
+struct PendingReplies {
+    repliers: Vec<String>,
+    sender: mpsc::UnboundedSender<Reply>,
+}
+
+static PENDING_REPLIES: LazyLock<Mutex<HashMap<u64, PendingReplies>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Starts waiting for the replies of `repliers`. They are received until [`finish`] is called
+pub async fn register(repliers: Vec<String>) -> (u64, mpsc::UnboundedReceiver<Reply>) {
+    let request_id = NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed);
+    let (sender, receiver) = mpsc::unbounded_channel();
+
+    PENDING_REPLIES
+        .lock()
+        .await
+        .insert(request_id, PendingReplies { repliers, sender });
+
+    (request_id, receiver)
+}
+
+/// Hands the reply to whoever waits for it, returns false when nobody waits for a reply
+/// of the caller with that id
+pub async fn reply(request_id: u64, caller: &str, body: &[u8]) -> bool {
+    let mut pending = PENDING_REPLIES.lock().await;
+
+    let Some(replies) = pending.get_mut(&request_id) else {
+        return false;
+    };
+
+    let Some(position) = replies.repliers.iter().position(|r| r == caller) else {
+        return false;
+    };
+    replies.repliers.swap_remove(position);
+
+    replies
+        .sender
+        .send(Reply {
+            caller: caller.to_string(),
+            body: body.to_vec(),
+        })
+        .is_ok()
+}
+
+/// Stops waiting for replies, the late ones are rejected
+pub async fn finish(request_id: u64) {
+    PENDING_REPLIES.lock().await.remove(&request_id);
+}
@@ -0,0 +1,278 @@
+use crate::handlers;
+use crate::registry::unix_time_millis;
+use crate::store;
+use chrono::{Local, TimeZone};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::LazyLock;
+use std::time::Duration;
+use tokio::sync::{Mutex, Notify};
+use tracing::{error, info, warn};
+use trtcp::{Action, ActionType, Head, Request, StatusType};
+
+/// Header with the id of a schedule, on its invokes and on cancel requests
+pub const SCHEDULE_ID_HEADER: &str = "schedule-id";
+
+/// An invoke the broker makes on behalf of a client, once after a delay or on every
+/// occurrence of a cron expression
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Schedule {
+    pub id: u64,
+    /// Event to invoke, as `module:id`
+    pub event: String,
+    pub caller: String,
+    pub body: Vec<u8>,
+    /// Recurring schedules have it, the others are removed after they fire
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cron: Option<String>,
+    /// Unix time in milliseconds of the next invoke
+    pub next_run: u64,
+}
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("Invalid cron expression: {0}")]
+    InvalidCron(#[from] cron::error::Error),
+    #[error("The cron expression has no upcoming time")]
+    NoUpcomingRun,
+    #[error("The delay is too long")]
+    DelayTooLong,
+    #[error(transparent)]
+    Store(#[from] store::Error),
+}
+
+static SCHEDULES: LazyLock<Mutex<BTreeMap<u64, Schedule>>> =
+    LazyLock::new(|| Mutex::new(BTreeMap::new()));
+
+static NEXT_ID: AtomicU64 = AtomicU64::new(1);
+
+/// Wakes up the runner when the schedules change
+static CHANGED: LazyLock<Notify> = LazyLock::new(Notify::new);
+
+/// Parses a cron expression in the local time of the broker. Besides the formats of the
+/// `cron` crate, with seconds, it takes the usual five fields `minute hour day month weekday`
+fn parse_cron(expression: &str) -> Result<cron::Schedule, Error> {
+    let expression = if expression.split_whitespace().count() == 5 {
+        format!("0 {}", expression)
+    } else {
+        expression.to_string()
+    };
+
+    Ok(cron::Schedule::from_str(&expression)?)
+}
+
+/// Unix time in milliseconds of the first occurrence of the expression after `after`
+fn next_occurrence(expression: &str, after: u64) -> Result<u64, Error> {
+    let after = Local
+        .timestamp_millis_opt(after as i64)
+        .single()
+        .ok_or(Error::NoUpcomingRun)?;
+
+    parse_cron(expression)?
+        .after(&after)
+        .next()
+        .map(|next| next.timestamp_millis() as u64)
+        .ok_or(Error::NoUpcomingRun)
+}
+
+/// Fills the schedules with the ones of the store, returns how many there are. Recurring
+/// schedules that were due while the broker was down skip to their next occurrence, the
+/// delayed ones fire right away
+pub async fn load() -> Result<usize, store::Error> {
+    let mut schedules = SCHEDULES.lock().await;
+    let now = unix_time_millis();
+
+    for mut schedule in store::store().load_schedules()? {
+        if let Some(cron) = &schedule.cron {
+            if schedule.next_run < now {
+                match next_occurrence(cron, now) {
+                    Ok(next_run) => schedule.next_run = next_run,
+                    Err(e) => {
+                        error!("dropping the schedule {}: {}", schedule.id, e);
+                        continue;
+                    }
+                }
+            }
+        }
+
+        NEXT_ID.fetch_max(schedule.id + 1, Ordering::Relaxed);
+        schedules.insert(schedule.id, schedule);
+    }
+
+    Ok(schedules.len())
+}
+
+/// Schedules an invoke after `delay`, or on every occurrence of `cron`
+pub async fn add(
+    caller: &str,
+    event: &str,
+    body: &[u8],
+    delay: Option<Duration>,
+    cron: Option<&str>,
+) -> Result<Schedule, Error> {
+    let now = unix_time_millis();
+    let next_run = match cron {
+        Some(cron) => next_occurrence(cron, now)?,
+        None => u64::try_from(delay.unwrap_or_default().as_millis())
+            .ok()
+            .and_then(|delay| now.checked_add(delay))
+            .ok_or(Error::DelayTooLong)?,
+    };
+
+    let schedule = Schedule {
+        id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+        event: event.to_string(),
+        caller: caller.to_string(),
+        body: body.to_vec(),
+        cron: cron.map(str::to_string),
+        next_run,
+    };
+
+    store::store().save_schedule(&schedule)?;
+    SCHEDULES.lock().await.insert(schedule.id, schedule.clone());
+    CHANGED.notify_one();
+
+    Ok(schedule)
+}
+
+/// Schedules of the caller, or all of them when it's none
+pub async fn list(caller: Option<&str>) -> Vec<Schedule> {
+    SCHEDULES
+        .lock()
+        .await
+        .values()
+        .filter(|s| caller.is_none_or(|caller| s.caller == caller))
+        .cloned()
+        .collect()
+}
+
+pub async fn get(id: u64) -> Option<Schedule> {
+    SCHEDULES.lock().await.get(&id).cloned()
+}
+
+/// Removes the schedule, returns whether it existed
+pub async fn cancel(id: u64) -> Result<bool, store::Error> {
+    let mut schedules = SCHEDULES.lock().await;
+    if !schedules.contains_key(&id) {
+        return Ok(false);
+    }
+
+    store::store().delete_schedule(id)?;
+    schedules.remove(&id);
+    CHANGED.notify_one();
+
+    Ok(true)
+}
+
+/// Invokes the schedules when they are due, through the same handler as the invokes of
+/// the clients
+pub async fn run() {
+    loop {
+        let next_run = SCHEDULES.lock().await.values().map(|s| s.next_run).min();
+
+        let notified = CHANGED.notified();
+        match next_run {
+            Some(next_run) => {
+                let wait = Duration::from_millis(next_run.saturating_sub(unix_time_millis()));
+                tokio::select! {
+                    _ = tokio::time::sleep(wait) => {}
+                    _ = notified => continue,
+                }
+            }
+            None => {
+                notified.await;
+                continue;
+            }
+        }
+
+        for schedule in take_due().await {
+            fire(&schedule).await;
+        }
+    }
+}
+
+/// The schedules that are due. Recurring ones stay with their next occurrence, the others
+/// are removed
+async fn take_due() -> Vec<Schedule> {
+    let now = unix_time_millis();
+    let mut schedules = SCHEDULES.lock().await;
+
+    let due: Vec<u64> = schedules
+        .values()
+        .filter(|s| s.next_run <= now)
+        .map(|s| s.id)
+        .collect();
+
+    let mut fired = Vec::new();
+    for id in due {
+        let schedule = schedules.get_mut(&id).expect("Schedule not found");
+        fired.push(schedule.clone());
+
+        let next_run = schedule.cron.as_deref().map(|cron| next_occurrence(cron, now));
+        let saved = match next_run {
+            Some(Ok(next_run)) => {
+                schedule.next_run = next_run;
+                store::store().save_schedule(schedule)
+            }
+            Some(Err(e)) => {
+                warn!("schedule {} has no next occurrence: {}", id, e);
+                schedules.remove(&id);
+                store::store().delete_schedule(id)
+            }
+            None => {
+                schedules.remove(&id);
+                store::store().delete_schedule(id)
+            }
+        };
+
+        if let Err(e) = saved {
+            error!("could not persist the schedule {}: {}", id, e);
+        }
+    }
+
+    fired
+}
+
+async fn fire(schedule: &Schedule) {
+    info!("invoking the schedule {} on {}", schedule.id, schedule.event);
+
+    let (module, id) = schedule.event.split_once(':').unwrap_or((&schedule.event, ""));
+    let schedule_id = schedule.id.to_string();
+    let request = Request::new(
+        Head::new_with_version(&schedule.caller).with_header(SCHEDULE_ID_HEADER, &schedule_id),
+        Action::new(ActionType::Invoke, module, id),
+        schedule.body.as_slice(),
+    );
+    let (response, _) = handlers::handle_request(&request).await;
+
+    if *response.status().r#type() != StatusType::OK {
+        warn!(
+            "the schedule {} failed with status {}",
+            schedule.id,
+            response.status().r#type().name()
+        );
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_next_occurrence() {
+        let start = Local.with_ymd_and_hms(2025, 3, 10, 21, 30, 0).unwrap();
+        let start = start.timestamp_millis() as u64;
+
+        let closing = Local.with_ymd_and_hms(2025, 3, 10, 22, 0, 0).unwrap();
+        assert_eq!(next_occurrence("0 22 * * *", start).unwrap(), closing.timestamp_millis() as u64);
+
+        let next_day = Local.with_ymd_and_hms(2025, 3, 11, 22, 0, 0).unwrap();
+        let after_closing = closing.timestamp_millis() as u64;
+        assert_eq!(next_occurrence("0 22 * * *", after_closing).unwrap(), next_day.timestamp_millis() as u64);
+
+        assert_eq!(next_occurrence("*/15 * * * * *", start).unwrap(), start + 15_000);
+        assert!(matches!(next_occurrence("every day", start), Err(Error::InvalidCron(_))));
+    }
+}
@@ -0,0 +1,141 @@
+use crate::registry::{EventMetadata, Listener};
+use crate::scheduler::Schedule;
+use crate::store::{Error, EventStore};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::sync::Mutex;
+
+struct PendingFrame {
+    id: i64,
+    queued_at: u64,
+    frame: Vec<u8>,
+}
+
+/// Keeps everything in memory, so nothing survives a restart
+#[derive(Default)]
+pub struct MemoryStore {
+    events: Mutex<BTreeMap<String, EventMetadata>>,
+    subscriptions: Mutex<Vec<(String, Listener)>>,
+    pending: Mutex<(i64, HashMap<String, VecDeque<PendingFrame>>)>,
+    schedules: Mutex<BTreeMap<u64, Schedule>>,
+}
+
+impl EventStore for MemoryStore {
+    fn load_events(&self) -> Result<Vec<(String, EventMetadata)>, Error> {
+        let events = self.events.lock().unwrap();
+
+        Ok(events
+            .iter()
+            .map(|(name, metadata)| (name.clone(), metadata.clone()))
+            .collect())
+    }
+
+    fn load_subscriptions(&self) -> Result<Vec<(String, Listener)>, Error> {
+        Ok(self.subscriptions.lock().unwrap().clone())
+    }
+
+    fn save_event(&self, name: &str, metadata: &EventMetadata) -> Result<(), Error> {
+        self.events
+            .lock()
+            .unwrap()
+            .insert(name.to_string(), metadata.clone());
+        Ok(())
+    }
+
+    fn delete_event(&self, name: &str) -> Result<(), Error> {
+        self.events.lock().unwrap().remove(name);
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|(event, _)| event != name);
+        Ok(())
+    }
+
+    fn save_subscription(&self, event: &str, listener: &Listener) -> Result<(), Error> {
+        let mut subscriptions = self.subscriptions.lock().unwrap();
+
+        match subscriptions
+            .iter_mut()
+            .find(|(e, l)| e == event && l.name == listener.name)
+        {
+            Some((_, saved)) => *saved = listener.clone(),
+            None => subscriptions.push((event.to_string(), listener.clone())),
+        }
+        Ok(())
+    }
+
+    fn delete_subscription(&self, event: &str, listener: &str) -> Result<(), Error> {
+        self.subscriptions
+            .lock()
+            .unwrap()
+            .retain(|(e, l)| e != event || l.name != listener);
+        Ok(())
+    }
+
+    fn enqueue_pending(&self, client: &str, frame: &[u8], queued_at: u64) -> Result<(), Error> {
+        let mut guard = self.pending.lock().unwrap();
+        let (last_id, queues) = &mut *guard;
+        *last_id += 1;
+
+        queues
+            .entry(client.to_string())
+            .or_default()
+            .push_back(PendingFrame {
+                id: *last_id,
+                queued_at,
+                frame: frame.to_vec(),
+            });
+        Ok(())
+    }
+
+    fn pending(&self, client: &str) -> Result<Vec<(i64, Vec<u8>)>, Error> {
+        let guard = self.pending.lock().unwrap();
+
+        Ok(guard
+            .1
+            .get(client)
+            .map(|queue| queue.iter().map(|p| (p.id, p.frame.clone())).collect())
+            .unwrap_or_default())
+    }
+
+    fn remove_pending(&self, client: &str, last: i64) -> Result<(), Error> {
+        if let Some(queue) = self.pending.lock().unwrap().1.get_mut(client) {
+            queue.retain(|p| p.id > last);
+        }
+        Ok(())
+    }
+
+    fn prune_pending(&self, client: &str, max_bytes: usize, oldest: u64) -> Result<(), Error> {
+        let mut guard = self.pending.lock().unwrap();
+        let Some(queue) = guard.1.get_mut(client) else {
+            return Ok(());
+        };
+
+        queue.retain(|p| p.queued_at >= oldest);
+
+        let mut size: usize = queue.iter().map(|p| p.frame.len()).sum();
+        while size > max_bytes {
+            match queue.pop_front() {
+                Some(p) => size -= p.frame.len(),
+                None => break,
+            }
+        }
+        Ok(())
+    }
+
+    fn load_schedules(&self) -> Result<Vec<Schedule>, Error> {
+        Ok(self.schedules.lock().unwrap().values().cloned().collect())
+    }
+
+    fn save_schedule(&self, schedule: &Schedule) -> Result<(), Error> {
+        self.schedules
+            .lock()
+            .unwrap()
+            .insert(schedule.id, schedule.clone());
+        Ok(())
+    }
+
+    fn delete_schedule(&self, id: u64) -> Result<(), Error> {
+        self.schedules.lock().unwrap().remove(&id);
+        Ok(())
+    }
+}
@@ -0,0 +1,115 @@
+mod memory;
+mod sqlite;
+
+use crate::config::Config;
+use crate::registry::{unix_time, EventMetadata, Listener};
+use crate::scheduler::Schedule;
+use std::sync::OnceLock;
+
+pub use memory::MemoryStore;
+pub use sqlite::SqliteStore;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("SQLite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("Serialization error: {0}")]
+    Serialization(#[from] serde_json::Error),
+}
+
+/// Persistence of the event registry. While the broker runs the registry in memory is
+/// the source of truth, the store only has to give it back on startup
+pub trait EventStore: Send + Sync {
+    fn load_events(&self) -> Result<Vec<(String, EventMetadata)>, Error>;
+
+    /// Durable subscriptions, in the order they were saved
+    fn load_subscriptions(&self) -> Result<Vec<(String, Listener)>, Error>;
+
+    fn save_event(&self, name: &str, metadata: &EventMetadata) -> Result<(), Error>;
+
+    /// Deletes the event and its subscriptions
+    fn delete_event(&self, name: &str) -> Result<(), Error>;
+
+    fn save_subscription(&self, event: &str, listener: &Listener) -> Result<(), Error>;
+
+    fn delete_subscription(&self, event: &str, listener: &str) -> Result<(), Error>;
+
+    /// Keeps a frame that couldn't be delivered to an offline client
+    fn enqueue_pending(&self, client: &str, frame: &[u8], queued_at: u64) -> Result<(), Error>;
+
+    /// Frames waiting for the client with their id, oldest first
+    fn pending(&self, client: &str) -> Result<Vec<(i64, Vec<u8>)>, Error>;
+
+    /// Removes the frames of the client up to the id `last`, included
+    fn remove_pending(&self, client: &str, last: i64) -> Result<(), Error>;
+
+    /// Drops the frames queued before `oldest` and then the oldest ones until the queue of
+    /// the client fits into `max_bytes`
+    fn prune_pending(&self, client: &str, max_bytes: usize, oldest: u64) -> Result<(), Error>;
+
+    fn load_schedules(&self) -> Result<Vec<Schedule>, Error>;
+
+    /// Saves a new schedule or the next run of an existing one
+    fn save_schedule(&self, schedule: &Schedule) -> Result<(), Error>;
+
+    fn delete_schedule(&self, id: u64) -> Result<(), Error>;
+}
+
+/// Bounds of the queue kept for each offline client
+#[derive(Debug, Clone, Copy)]
+struct OfflineLimits {
+    max_bytes: usize,
+    max_age: u64,
+}
+
+static STORE: OnceLock<Box<dyn EventStore>> = OnceLock::new();
+static OFFLINE_LIMITS: OnceLock<OfflineLimits> = OnceLock::new();
+
+pub fn init(config: &Config) -> Result<(), Error> {
+    let store: Box<dyn EventStore> = match &config.sqlite {
+        Some(path) => Box::new(SqliteStore::open(path)?),
+        None => Box::new(MemoryStore::default()),
+    };
+
+    let _ = STORE.set(store);
+    let _ = OFFLINE_LIMITS.set(OfflineLimits {
+        max_bytes: config.offline_max_bytes,
+        max_age: config.offline_max_age,
+    });
+    Ok(())
+}
+
+/// The store chosen on startup, the memory one if none was
+pub fn store() -> &'static dyn EventStore {
+    STORE
+        .get_or_init(|| Box::new(MemoryStore::default()))
+        .as_ref()
+}
+
+fn offline_limits() -> OfflineLimits {
+    *OFFLINE_LIMITS.get_or_init(|| OfflineLimits {
+        max_bytes: 1024 * 1024,
+        max_age: 3600,
+    })
+}
+
+/// Queues a frame for an offline client, dropping what goes over the limits
+pub fn enqueue_offline(client: &str, frame: &[u8]) -> Result<(), Error> {
+    let limits = offline_limits();
+    let now = unix_time();
+
+    store().enqueue_pending(client, frame, now)?;
+    store().prune_pending(client, limits.max_bytes, now.saturating_sub(limits.max_age))
+}
+
+/// Frames still within the limits that are waiting for the client, oldest first
+pub fn offline_frames(client: &str) -> Result<Vec<(i64, Vec<u8>)>, Error> {
+    let limits = offline_limits();
+
+    store().prune_pending(
+        client,
+        limits.max_bytes,
+        unix_time().saturating_sub(limits.max_age),
+    )?;
+    store().pending(client)
+}
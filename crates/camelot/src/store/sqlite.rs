@@ -0,0 +1,214 @@
+use crate::registry::{EventMetadata, Listener};
+use crate::scheduler::Schedule;
+use crate::store::{Error, EventStore};
+use rusqlite::{params, Connection};
+use std::path::Path;
+use std::sync::Mutex;
+
+/// Persists the registry into a SQLite database. Metadata, subscription options and
+/// schedules are saved as JSON, so new fields don't need a migration
+pub struct SqliteStore {
+    connection: Mutex<Connection>,
+}
+
+impl SqliteStore {
+    pub fn open(path: &Path) -> Result<Self, Error> {
+        let connection = Connection::open(path)?;
+
+        connection.execute_batch(
+            "CREATE TABLE IF NOT EXISTS events (
+                name TEXT PRIMARY KEY,
+                metadata TEXT NOT NULL
+            );
+            CREATE TABLE IF NOT EXISTS subscriptions (
+                event TEXT NOT NULL,
+                listener TEXT NOT NULL,
+                options TEXT NOT NULL,
+                PRIMARY KEY (event, listener)
+            );
+            CREATE TABLE IF NOT EXISTS pending (
+                id INTEGER PRIMARY KEY AUTOINCREMENT,
+                client TEXT NOT NULL,
+                queued_at INTEGER NOT NULL,
+                frame BLOB NOT NULL
+            );
+            CREATE INDEX IF NOT EXISTS pending_client ON pending (client, id);
+            CREATE TABLE IF NOT EXISTS schedules (
+                id INTEGER PRIMARY KEY,
+                schedule TEXT NOT NULL
+            );",
+        )?;
+
+        Ok(Self {
+            connection: Mutex::new(connection),
+        })
+    }
+}
+
+impl EventStore for SqliteStore {
+    fn load_events(&self) -> Result<Vec<(String, EventMetadata)>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT name, metadata FROM events")?;
+
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(name, metadata)| Ok((name, serde_json::from_str(&metadata)?)))
+            .collect()
+    }
+
+    fn load_subscriptions(&self) -> Result<Vec<(String, Listener)>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT event, options FROM subscriptions ORDER BY rowid")?;
+
+        let rows = statement
+            .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.into_iter()
+            .map(|(event, options)| Ok((event, serde_json::from_str(&options)?)))
+            .collect()
+    }
+
+    fn save_event(&self, name: &str, metadata: &EventMetadata) -> Result<(), Error> {
+        let metadata = serde_json::to_string(metadata)?;
+
+        self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO events (name, metadata) VALUES (?1, ?2)",
+            params![name, metadata],
+        )?;
+        Ok(())
+    }
+
+    fn delete_event(&self, name: &str) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute("DELETE FROM subscriptions WHERE event = ?1", params![name])?;
+        transaction.execute("DELETE FROM events WHERE name = ?1", params![name])?;
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn save_subscription(&self, event: &str, listener: &Listener) -> Result<(), Error> {
+        let options = serde_json::to_string(listener)?;
+
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO subscriptions (event, listener, options) VALUES (?1, ?2, ?3)
+             ON CONFLICT (event, listener) DO UPDATE SET options = excluded.options",
+            params![event, listener.name, options],
+        )?;
+        Ok(())
+    }
+
+    fn delete_subscription(&self, event: &str, listener: &str) -> Result<(), Error> {
+        self.connection.lock().unwrap().execute(
+            "DELETE FROM subscriptions WHERE event = ?1 AND listener = ?2",
+            params![event, listener],
+        )?;
+        Ok(())
+    }
+
+    fn enqueue_pending(&self, client: &str, frame: &[u8], queued_at: u64) -> Result<(), Error> {
+        self.connection.lock().unwrap().execute(
+            "INSERT INTO pending (client, queued_at, frame) VALUES (?1, ?2, ?3)",
+            params![client, queued_at, frame],
+        )?;
+        Ok(())
+    }
+
+    fn pending(&self, client: &str) -> Result<Vec<(i64, Vec<u8>)>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement =
+            connection.prepare("SELECT id, frame FROM pending WHERE client = ?1 ORDER BY id")?;
+
+        let frames = statement
+            .query_map(params![client], |row| Ok((row.get(0)?, row.get(1)?)))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(frames)
+    }
+
+    fn remove_pending(&self, client: &str, last: i64) -> Result<(), Error> {
+        self.connection.lock().unwrap().execute(
+            "DELETE FROM pending WHERE client = ?1 AND id <= ?2",
+            params![client, last],
+        )?;
+        Ok(())
+    }
+
+    fn prune_pending(&self, client: &str, max_bytes: usize, oldest: u64) -> Result<(), Error> {
+        let mut connection = self.connection.lock().unwrap();
+        let transaction = connection.transaction()?;
+
+        transaction.execute(
+            "DELETE FROM pending WHERE client = ?1 AND queued_at < ?2",
+            params![client, oldest],
+        )?;
+
+        // The newest frames that fit into max_bytes are kept, everything before the first
+        // one that doesn't fit is dropped
+        let cutoff: Option<i64> = {
+            let mut statement = transaction.prepare(
+                "SELECT id, length(frame) FROM pending WHERE client = ?1 ORDER BY id DESC",
+            )?;
+            let mut rows = statement.query(params![client])?;
+
+            let mut size = 0usize;
+            let mut cutoff = None;
+            while let Some(row) = rows.next()? {
+                size += row.get::<_, i64>(1)? as usize;
+                if size > max_bytes {
+                    cutoff = Some(row.get(0)?);
+                    break;
+                }
+            }
+            cutoff
+        };
+
+        if let Some(cutoff) = cutoff {
+            transaction.execute(
+                "DELETE FROM pending WHERE client = ?1 AND id <= ?2",
+                params![client, cutoff],
+            )?;
+        }
+
+        transaction.commit()?;
+        Ok(())
+    }
+
+    fn load_schedules(&self) -> Result<Vec<Schedule>, Error> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection.prepare("SELECT schedule FROM schedules ORDER BY id")?;
+
+        let rows = statement
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        rows.iter()
+            .map(|schedule| Ok(serde_json::from_str(schedule)?))
+            .collect()
+    }
+
+    fn save_schedule(&self, schedule: &Schedule) -> Result<(), Error> {
+        let json = serde_json::to_string(schedule)?;
+
+        self.connection.lock().unwrap().execute(
+            "INSERT OR REPLACE INTO schedules (id, schedule) VALUES (?1, ?2)",
+            params![schedule.id as i64, json],
+        )?;
+        Ok(())
+    }
+
+    fn delete_schedule(&self, id: u64) -> Result<(), Error> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute("DELETE FROM schedules WHERE id = ?1", params![id as i64])?;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use crate::Error;
+use std::path::Path;
+use std::sync::Arc;
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio_rustls::client::TlsStream;
+use tokio_rustls::rustls::crypto::{ring, CryptoProvider};
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer, ServerName};
+use tokio_rustls::rustls::server::WebPkiClientVerifier;
+use tokio_rustls::rustls::{ClientConfig, RootCertStore, ServerConfig};
+
+pub use tokio_rustls::{rustls, TlsAcceptor, TlsConnector};
+
+fn provider() -> Arc<CryptoProvider> {
+    Arc::new(ring::default_provider())
+}
+
+/// Loads every certificate of a PEM file, the first one is the leaf when it's a chain
+pub fn load_certs(path: &Path) -> Result<Vec<CertificateDer<'static>>, Error> {
+    let certs = CertificateDer::pem_file_iter(path)
+        .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+        .map_err(|e| Error::InvalidCertificate(format!("{}: {}", path.display(), e)))?;
+
+    if certs.is_empty() {
+        return Err(Error::InvalidCertificate(format!(
+            "{}: no certificates found",
+            path.display()
+        )));
+    }
+
+    Ok(certs)
+}
+
+pub fn load_private_key(path: &Path) -> Result<PrivateKeyDer<'static>, Error> {
+    PrivateKeyDer::from_pem_file(path)
+        .map_err(|e| Error::InvalidCertificate(format!("{}: {}", path.display(), e)))
+}
+
+fn load_roots(ca_path: &Path) -> Result<RootCertStore, Error> {
+    let mut roots = RootCertStore::empty();
+    for cert in load_certs(ca_path)? {
+        roots.add(cert)?;
+    }
+
+    Ok(roots)
+}
+
+/// Builds the broker side configuration. When `client_ca_path` is given every client
+/// has to present a certificate signed by that CA (mutual TLS)
+pub fn server_config(
+    cert_path: &Path,
+    key_path: &Path,
+    client_ca_path: Option<&Path>,
+) -> Result<Arc<ServerConfig>, Error> {
+    let builder =
+        ServerConfig::builder_with_provider(provider()).with_safe_default_protocol_versions()?;
+
+    let builder = match client_ca_path {
+        Some(ca_path) => {
+            let roots = Arc::new(load_roots(ca_path)?);
+            let verifier = WebPkiClientVerifier::builder_with_provider(roots, provider())
+                .build()
+                .map_err(|e| Error::InvalidCertificate(e.to_string()))?;
+            builder.with_client_cert_verifier(verifier)
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    let config = builder.with_single_cert(load_certs(cert_path)?, load_private_key(key_path)?)?;
+
+    Ok(Arc::new(config))
+}
+
+/// Builds the client side configuration trusting the CA of `ca_path`. `identity` is the
+/// certificate and key pair presented to brokers that require mutual TLS
+pub fn client_config(
+    ca_path: &Path,
+    identity: Option<(&Path, &Path)>,
+) -> Result<Arc<ClientConfig>, Error> {
+    let builder = ClientConfig::builder_with_provider(provider())
+        .with_safe_default_protocol_versions()?
+        .with_root_certificates(load_roots(ca_path)?);
+
+    let config = match identity {
+        Some((cert_path, key_path)) => {
+            builder.with_client_auth_cert(load_certs(cert_path)?, load_private_key(key_path)?)?
+        }
+        None => builder.with_no_client_auth(),
+    };
+
+    Ok(Arc::new(config))
+}
+
+/// Opens a TCP connection and runs the TLS handshake over it. The result can be passed to
+/// [`crate::split`]
+pub async fn connect<A: ToSocketAddrs>(
+    addr: A,
+    server_name: &str,
+    config: Arc<ClientConfig>,
+) -> Result<TlsStream<TcpStream>, Error> {
+    let server_name = ServerName::try_from(server_name.to_string())
+        .map_err(|e| Error::InvalidCertificate(e.to_string()))?;
+
+    let stream = TcpStream::connect(addr).await?;
+    let stream = TlsConnector::from(config).connect(server_name, stream).await?;
+
+    Ok(stream)
+}
+
+/// Common name (CN) of the certificate subject
+pub fn common_name(cert: &CertificateDer<'_>) -> Option<String> {
+    let (_, cert) = x509_parser::parse_x509_certificate(cert).ok()?;
+    let cn = cert.subject().iter_common_name().next()?;
+
+    cn.as_str().ok().map(str::to_string)
+}
@@ -0,0 +1,195 @@
+use crate::{handle_client, metrics};
+use bytes::Bytes;
+use futures_util::{future, SinkExt, TryStreamExt};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::io;
+use std::net::SocketAddr;
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::TcpListener;
+use tokio::sync::mpsc;
+use tokio_util::codec::{FramedRead, FramedWrite, LinesCodec};
+use tokio_util::io::{CopyToBytes, SinkWriter, StreamReader};
+use tokio_util::sync::PollSender;
+use tracing::{error, info};
+use trtcp::{Action, ActionType, Head, Request, Response, StatusType};
+
+const MAX_LINE_LEN: usize = 16 * 1024 * 1024;
+const LINES_BUFFER: usize = 64;
+/// Bytes trtcp splits the head of a frame on
+const SEPARATORS: [char; 3] = ['\u{1F}', '\u{1E}', '\u{1D}'];
+
+/// One line of the JSON protocol. Requests have an `action` and responses a `status`,
+/// the rest of the fields map to the same trtcp sections
+#[derive(Serialize, Deserialize, Debug, Default)]
+struct JsonFrame {
+    #[serde(default)]
+    caller: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    headers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    action: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    event: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    status: Option<String>,
+    #[serde(default)]
+    body: String,
+}
+
+pub async fn serve(addr: SocketAddr) {
+    let listener = TcpListener::bind(addr)
+        .await
+        .unwrap_or_else(|_| panic!("Could not bind the json listener to {}", addr));
+
+    info!("camelot initialized on json {}", addr);
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, client_addr)) => {
+                metrics::CONNECTIONS.with_label_values(&["json"]).inc();
+                tokio::spawn(async move {
+                    let (read_half, write_half) = socket.into_split();
+
+                    // Lines go through a channel, so the reader can answer malformed ones
+                    // without going through the broker
+                    let (lines_tx, lines_rx) = mpsc::channel(LINES_BUFFER);
+                    tokio::spawn(write_lines(write_half, lines_rx));
+
+                    let invalid_tx = lines_tx.clone();
+                    let reader = StreamReader::new(
+                        FramedRead::new(read_half, LinesCodec::new_with_max_length(MAX_LINE_LEN))
+                            .map_err(io::Error::other)
+                            .try_filter(|line| future::ready(!line.trim().is_empty()))
+                            .try_filter_map(move |line| {
+                                let invalid_tx = invalid_tx.clone();
+                                Box::pin(async move {
+                                    match json_to_frame(&line) {
+                                        Ok(frame) => Ok(Some(Bytes::from(frame))),
+                                        Err(e) => {
+                                            let _ = invalid_tx.send(invalid_request(&e)).await;
+                                            Ok(None)
+                                        }
+                                    }
+                                })
+                            }),
+                    );
+
+                    // Every frame is written with a single write, so each one becomes a line
+                    let writer = SinkWriter::new(CopyToBytes::new(
+                        PollSender::new(lines_tx)
+                            .sink_map_err(io::Error::other)
+                            .with(|frame: Bytes| future::ready(frame_to_json(&frame))),
+                    ));
+
+                    let (reader, writer) = camelot::from_parts(reader, writer, "tmp");
+                    handle_client(reader, writer, format!("json://{}", client_addr), None).await;
+                });
+            }
+            Err(e) => error!("couldn't get client connection: {:?}", e),
+        }
+    }
+}
+
+async fn write_lines(write_half: OwnedWriteHalf, mut lines: mpsc::Receiver<String>) {
+    let mut sink = FramedWrite::new(write_half, LinesCodec::new());
+    while let Some(line) = lines.recv().await {
+        if sink.send(line).await.is_err() {
+            break;
+        }
+    }
+}
+
+/// Answer to a line that isn't a valid request, the connection stays open
+fn invalid_request(error: &io::Error) -> String {
+    let message = JsonFrame {
+        status: Some(StatusType::InvalidRequest.name().to_string()),
+        body: error.to_string(),
+        ..Default::default()
+    };
+
+    serde_json::to_string(&message).expect("Could not serialize the response")
+}
+
+fn invalid_data<E: Into<Box<dyn std::error::Error + Send + Sync>>>(e: E) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, e)
+}
+
+fn json_to_frame(line: &str) -> Result<Vec<u8>, io::Error> {
+    let message: JsonFrame = serde_json::from_str(line).map_err(invalid_data)?;
+
+    let r#type: ActionType = message
+        .action
+        .as_deref()
+        .ok_or_else(|| invalid_data("missing action"))?
+        .parse()
+        .map_err(invalid_data)?;
+
+    let (module, id) = match &message.event {
+        Some(event) => event
+            .split_once(':')
+            .ok_or_else(|| invalid_data("the event must be module:id"))?,
+        None => ("", ""),
+    };
+
+    // They would split the frame somewhere else and the broker would drop the connection
+    let fields = [message.caller.as_str(), module, id]
+        .into_iter()
+        .chain(message.headers.iter().flat_map(|(key, value)| [key.as_str(), value.as_str()]));
+    for field in fields {
+        if field.contains(SEPARATORS) {
+            return Err(invalid_data("the caller, the event and the headers can't contain separators"));
+        }
+    }
+
+    let head = message
+        .headers
+        .iter()
+        .fold(Head::new_with_version(&message.caller), |head, (key, value)| {
+            head.with_header(key, value)
+        });
+
+    let request = Request::new(
+        head,
+        Action::new(r#type, module, id),
+        message.body.as_bytes(),
+    );
+
+    Ok(request.into())
+}
+
+fn frame_to_json(frame: &[u8]) -> Result<String, io::Error> {
+    let message = match frame.first() {
+        Some(0) => {
+            let request = Request::try_from(frame).map_err(invalid_data)?;
+            JsonFrame {
+                caller: request.head().caller().to_string(),
+                headers: headers(request.head()),
+                action: Some(request.action().r#type().name().to_string()),
+                event: Some(format!("{}:{}", request.action().module(), request.action().id())),
+                status: None,
+                body: String::from_utf8_lossy(request.body()).into_owned(),
+            }
+        }
+        _ => {
+            let response = Response::try_from(frame).map_err(invalid_data)?;
+            JsonFrame {
+                caller: response.head().caller().to_string(),
+                headers: headers(response.head()),
+                action: None,
+                event: None,
+                status: Some(response.status().r#type().name().to_string()),
+                body: String::from_utf8_lossy(response.body()).into_owned(),
+            }
+        }
+    };
+
+    serde_json::to_string(&message).map_err(invalid_data)
+}
+
+fn headers(head: &Head) -> BTreeMap<String, String> {
+    head.headers()
+        .iter()
+        .map(|(key, value)| (key.to_string(), value.to_string()))
+        .collect()
+}
@@ -0,0 +1,6 @@
+pub mod json;
+pub mod tcp;
+pub mod tls;
+pub mod ws;
+#[cfg(unix)]
+pub mod unix;
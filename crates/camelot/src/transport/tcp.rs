@@ -0,0 +1,47 @@
+use crate::config::Config;
+use crate::{handle_client, metrics};
+use tracing::{error, info};
+
+pub async fn serve(config: Config) {
+    let port = config.port;
+    let listener = tokio::net::TcpListener::bind(format!("0.0.0.0:{}", port))
+        .await
+        .unwrap_or_else(|_| panic!("Could not bind to port {}", port));
+
+    let tls_acceptor = super::tls::acceptor(&config);
+
+    info!(
+        "camelot initialized on port {}{}",
+        port,
+        if tls_acceptor.is_some() { " (tls)" } else { "" }
+    );
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, client_addr)) => {
+                let transport = if tls_acceptor.is_some() { "tls" } else { "tcp" };
+                metrics::CONNECTIONS.with_label_values(&[transport]).inc();
+
+                let tls_acceptor = tls_acceptor.clone();
+                let cn_mode = config.tls_cn;
+
+                tokio::spawn(async move {
+                    let Some(acceptor) = tls_acceptor else {
+                        let (reader, writer) = camelot::split(socket, "tmp").await;
+                        handle_client(reader, writer, client_addr.to_string(), None).await;
+                        return;
+                    };
+
+                    match super::tls::accept(&acceptor, socket, cn_mode).await {
+                        Ok((stream, identity)) => {
+                            let (reader, writer) = camelot::split(stream, "tmp").await;
+                            handle_client(reader, writer, client_addr.to_string(), identity).await
+                        }
+                        Err(e) => error!("tls handshake with {} failed: {}", client_addr, e),
+                    }
+                });
+            }
+            Err(e) => error!("couldn't get client connection: {:?}", e),
+        }
+    }
+}
@@ -0,0 +1,58 @@
+use crate::config::{CnMode, Config};
+use camelot::tls::TlsAcceptor;
+use camelot::Error;
+use tokio::net::TcpStream;
+use tokio_rustls::server::TlsStream;
+
+/// Caller name imposed by the certificate a client presented on a mutual TLS connection
+pub enum CertIdentity {
+    Set(String),
+    Check(String),
+}
+
+impl CertIdentity {
+    /// Resolves the name of the connection from the caller sent on connect. `None`
+    /// means the certificate doesn't allow that caller name
+    pub fn resolve<'a>(&'a self, caller: &'a str) -> Option<&'a str> {
+        match self {
+            CertIdentity::Set(cn) => Some(cn),
+            CertIdentity::Check(cn) if cn == caller => Some(caller),
+            CertIdentity::Check(_) => None,
+        }
+    }
+}
+
+pub fn acceptor(config: &Config) -> Option<TlsAcceptor> {
+    let cert = config.tls_cert.as_ref()?;
+    let key = config.tls_key.as_ref()?;
+
+    let server_config =
+        camelot::tls::server_config(cert, key, config.tls_client_ca.as_deref())
+            .unwrap_or_else(|e| panic!("Could not load the TLS configuration: {}", e));
+
+    Some(TlsAcceptor::from(server_config))
+}
+
+pub async fn accept(
+    acceptor: &TlsAcceptor,
+    socket: TcpStream,
+    cn_mode: CnMode,
+) -> Result<(TlsStream<TcpStream>, Option<CertIdentity>), Error> {
+    let stream = acceptor.accept(socket).await?;
+
+    let identity = match stream.get_ref().1.peer_certificates() {
+        Some([cert, ..]) => {
+            let cn = camelot::tls::common_name(cert).ok_or_else(|| {
+                Error::InvalidCertificate("client certificate without CN".to_string())
+            })?;
+
+            Some(match cn_mode {
+                CnMode::Set => CertIdentity::Set(cn),
+                CnMode::Check => CertIdentity::Check(cn),
+            })
+        }
+        _ => None,
+    };
+
+    Ok((stream, identity))
+}
@@ -0,0 +1,61 @@
+use crate::{handle_client, metrics};
+use std::fs::DirBuilder;
+use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+use std::path::{Path, PathBuf};
+use tokio::net::UnixListener;
+use tracing::{error, info};
+
+pub async fn serve(path: PathBuf, mode: u32) {
+    // A socket file left behind by a previous run would make the bind fail. Anything else
+    // at the path is most likely a wrong --unix value, so it's left alone
+    if let Ok(metadata) = std::fs::symlink_metadata(&path) {
+        if !metadata.file_type().is_socket() {
+            panic!("{:?} already exists and isn't a unix socket", path);
+        }
+        std::fs::remove_file(&path)
+            .unwrap_or_else(|_| panic!("Could not remove the stale socket {:?}", path));
+    }
+
+    let listener = bind_with_mode(&path, mode);
+
+    info!("camelot initialized on unix socket {:?} ({:o})", path, mode);
+
+    let mut connections = 0u64;
+    loop {
+        match listener.accept().await {
+            Ok((socket, _)) => {
+                metrics::CONNECTIONS.with_label_values(&["unix"]).inc();
+                connections += 1;
+                let client_addr = format!("{}#{}", path.display(), connections);
+
+                tokio::spawn(async move {
+                    let (reader, writer) = camelot::split(socket, "tmp").await;
+                    handle_client(reader, writer, client_addr, None).await;
+                });
+            }
+            Err(e) => error!("couldn't get client connection: {:?}", e),
+        }
+    }
+}
+
+/// Binds the socket in a private directory and moves it to the path once it has its
+/// permissions, so it's never reachable with the ones of the umask
+fn bind_with_mode(path: &Path, mode: u32) -> UnixListener {
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let staging = parent.join(format!(".camelot-{}", std::process::id()));
+    DirBuilder::new()
+        .mode(0o700)
+        .create(&staging)
+        .unwrap_or_else(|_| panic!("Could not create the directory {:?}", staging));
+
+    let staged = staging.join("socket");
+    let listener = UnixListener::bind(&staged)
+        .unwrap_or_else(|_| panic!("Could not bind to the unix socket {:?}", path));
+
+    std::fs::set_permissions(&staged, std::fs::Permissions::from_mode(mode))
+        .unwrap_or_else(|_| panic!("Could not set the permissions of {:?}", path));
+    std::fs::rename(&staged, path).unwrap_or_else(|_| panic!("Could not move the socket to {:?}", path));
+    let _ = std::fs::remove_dir(&staging);
+
+    listener
+}
@@ -0,0 +1,86 @@
+use crate::config::Config;
+use crate::{handle_client, metrics};
+use crate::transport::tls::CertIdentity;
+use bytes::Bytes;
+use futures_util::{future, SinkExt, StreamExt};
+use std::io;
+use std::net::SocketAddr;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::net::TcpListener;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_util::io::{CopyToBytes, SinkWriter, StreamReader};
+use tracing::{error, info};
+
+pub async fn serve(addr: SocketAddr, config: Config) {
+    let listener = TcpListener::bind(addr)
+        .await
+        .unwrap_or_else(|_| panic!("Could not bind the websocket listener to {}", addr));
+
+    let tls_acceptor = super::tls::acceptor(&config);
+
+    info!(
+        "camelot initialized on websocket {}{}",
+        addr,
+        if tls_acceptor.is_some() { " (tls)" } else { "" }
+    );
+
+    loop {
+        match listener.accept().await {
+            Ok((socket, client_addr)) => {
+                metrics::CONNECTIONS.with_label_values(&["ws"]).inc();
+                let tls_acceptor = tls_acceptor.clone();
+                let cn_mode = config.tls_cn;
+                let client_addr = format!("ws://{}", client_addr);
+
+                tokio::spawn(async move {
+                    let Some(acceptor) = tls_acceptor else {
+                        handle_socket(socket, client_addr, None).await;
+                        return;
+                    };
+
+                    match super::tls::accept(&acceptor, socket, cn_mode).await {
+                        Ok((stream, identity)) => handle_socket(stream, client_addr, identity).await,
+                        Err(e) => error!("tls handshake with {} failed: {}", client_addr, e),
+                    }
+                });
+            }
+            Err(e) => error!("couldn't get client connection: {:?}", e),
+        }
+    }
+}
+
+/// Runs the websocket handshake and adapts the messages into a byte stream of trtcp frames,
+/// so the client is handled exactly like the TCP ones
+async fn handle_socket<S>(stream: S, client_addr: String, identity: Option<CertIdentity>)
+where
+    S: AsyncRead + AsyncWrite + Send + Unpin + 'static,
+{
+    let socket = match tokio_tungstenite::accept_async(stream).await {
+        Ok(socket) => socket,
+        Err(e) => {
+            error!("websocket handshake with {} failed: {}", client_addr, e);
+            return;
+        }
+    };
+
+    let (sink, stream) = socket.split();
+
+    // Text, ping and pong messages don't carry trtcp frames
+    let reader = StreamReader::new(stream.filter_map(|message| {
+        future::ready(match message {
+            Ok(Message::Binary(bytes)) => Some(Ok(bytes)),
+            Ok(_) => None,
+            Err(e) => Some(Err(io::Error::other(e))),
+        })
+    }));
+
+    // Every frame is written with a single write followed by a flush, so each one
+    // becomes a single binary message
+    let writer = SinkWriter::new(CopyToBytes::new(
+        sink.sink_map_err(io::Error::other)
+            .with(|bytes: Bytes| future::ready(Ok::<_, io::Error>(Message::Binary(bytes)))),
+    ));
+
+    let (reader, writer) = camelot::from_parts(reader, writer, "tmp");
+    handle_client(reader, writer, client_addr, identity).await;
+}
@@ -0,0 +1,203 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use serde_json::Value;
+use server::TestServer;
+use std::time::Duration;
+use trtcp::{ActionType, StatusType};
+
+const ADDR: &str = "localhost:1254";
+
+#[tokio::test]
+async fn unacknowledged_callbacks_are_redelivered_and_dead_lettered() {
+    let _server = TestServer::start(1254, &["--ack-timeout-ms", "200", "--max-attempts", "2"]).await;
+
+    let mut register = Client::connect(ADDR, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("order").await.status().r#type(), StatusType::OK);
+
+    let mut auditor = Client::connect(ADDR, "auditor").await;
+    assert_eq!(*auditor.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = auditor
+        .request(ActionType::Listen, "camelot:deadLetter", &[], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    let mut printer = Client::connect(ADDR, "printer").await;
+    assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = printer
+        .listen_event_with_headers("order", &[("delivery", "ack")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    // Acknowledged callbacks aren't sent again
+    register.invoke_event("order", "first".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+
+    let callback = printer.read_request().await;
+    assert_eq!(*callback.body(), "first".as_bytes());
+    let delivery_id = callback.head().header("delivery-id").unwrap().to_string();
+    let response = printer
+        .request(ActionType::Ack, "test:order", &[("delivery-id", &delivery_id)], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    // The second one is never acknowledged
+    register.invoke_event("order", "second".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+
+    let callback = printer.read_request().await;
+    assert_eq!(*callback.body(), "second".as_bytes());
+    assert_eq!(callback.head().header("redelivered"), None);
+    let delivery_id = callback.head().header("delivery-id").unwrap().to_string();
+
+    let callback = printer.read_request().await;
+    assert_eq!(*callback.body(), "second".as_bytes());
+    assert_eq!(callback.head().header("redelivered"), Some("true"));
+    assert_eq!(callback.head().header("delivery-id"), Some(delivery_id.as_str()));
+
+    let letter = auditor.read_request().await;
+    assert_eq!(letter.head().caller(), "camelot");
+    let letter: Value = serde_json::from_slice(letter.body()).unwrap();
+    assert_eq!(letter["event"], "test:order");
+    assert_eq!(letter["listener"], "printer");
+    assert_eq!(letter["caller"], "register");
+    assert_eq!(letter["attempts"], 2);
+    assert_eq!(letter["body"], "second");
+
+    let response = printer
+        .request(ActionType::Ack, "test:order", &[("delivery-id", &delivery_id)], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::InvalidRequest);
+
+    // Pending callbacks are dropped when the listener leaves or the event is deleted
+    register.invoke_event("order", "third".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    assert_eq!(*printer.read_request().await.body(), "third".as_bytes());
+    let response = printer.request(ActionType::Leave, "test:order", &[], &[]).await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    let response = printer
+        .listen_event_with_headers("order", &[("delivery", "ack")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    register.invoke_event("order", "fourth".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    assert_eq!(*printer.read_request().await.body(), "fourth".as_bytes());
+    let response = register.request(ActionType::Delete, "test:order", &[], &[]).await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    assert_eq!(printer.read_request().await.head().header("deleted"), Some("true"));
+
+    let letter = tokio::time::timeout(Duration::from_millis(700), auditor.read_request()).await;
+    assert!(letter.is_err(), "a purged callback was dead-lettered");
+}
+
+#[tokio::test]
+async fn consumer_groups_share_callbacks() {
+    let addr = "localhost:1263";
+    let _server = TestServer::start(1263, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("print").await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("reprint").await.status().r#type(), StatusType::OK);
+
+    let mut printers = Vec::new();
+    for name in ["printer1", "printer2"] {
+        let mut printer = Client::connect(addr, name).await;
+        assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+        let response = printer.listen_event_with_headers("print", &[("group", "printers")]).await;
+        assert_eq!(*response.status().r#type(), StatusType::OK);
+        let response = printer
+            .listen_event_with_headers("reprint", &[("group", "printers"), ("delivery", "ack")])
+            .await;
+        assert_eq!(*response.status().r#type(), StatusType::OK);
+        printers.push(printer);
+    }
+
+    let mut auditor = Client::connect(addr, "auditor").await;
+    assert_eq!(*auditor.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*auditor.listen_event("print").await.status().r#type(), StatusType::OK);
+
+    // The members take turns and the auditor gets all of them
+    for ticket in ["1", "2", "3", "4"] {
+        register.invoke_event("print", ticket.as_bytes()).await;
+        assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    }
+
+    for ticket in ["1", "2", "3", "4"] {
+        assert_eq!(*auditor.read_request().await.body(), ticket.as_bytes());
+    }
+    let first = printers[0].read_request().await.body().to_vec();
+    let second = printers[1].read_request().await.body().to_vec();
+    assert_ne!(first, second);
+    assert_ne!(printers[0].read_request().await.body().to_vec(), first);
+    assert_ne!(printers[1].read_request().await.body().to_vec(), second);
+
+    // In ack mode the member with the least unacknowledged callbacks gets the next one
+    register.invoke_event("reprint", b"5").await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    register.invoke_event("reprint", b"6").await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+
+    // printer1 never acknowledges 5, so 6 and 7 go to printer2
+    assert_eq!(*printers[0].read_request().await.body(), b"5");
+    let callback = printers[1].read_request().await;
+    assert_eq!(*callback.body(), b"6");
+    let delivery_id = callback.head().header("delivery-id").unwrap().to_string();
+    let response = printers[1]
+        .request(ActionType::Ack, "test:reprint", &[("delivery-id", &delivery_id)], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    register.invoke_event("reprint", b"7").await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    assert_eq!(*printers[1].read_request().await.body(), b"7");
+}
+
+#[tokio::test]
+async fn dead_letters_are_kept_for_history() {
+    let addr = "localhost:1273";
+    let _server = TestServer::start(1273, &["--ack-timeout-ms", "100", "--max-attempts", "1"]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("order").await.status().r#type(), StatusType::OK);
+
+    let mut printer = Client::connect(addr, "printer").await;
+    assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = printer
+        .listen_event_with_headers("order", &[("delivery", "ack")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    // Nobody listens to the dead-letter event when the callback runs out of attempts
+    register.invoke_event("order", "lost".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    assert_eq!(*printer.read_request().await.body(), "lost".as_bytes());
+
+    let mut auditor = Client::connect(addr, "auditor").await;
+    assert_eq!(*auditor.establish_connection().await.status().r#type(), StatusType::OK);
+
+    let mut count = String::new();
+    for _ in 0..20 {
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let response = auditor
+            .request(ActionType::History, "camelot:deadLetter", &[], &[])
+            .await;
+        assert_eq!(*response.status().r#type(), StatusType::OK);
+        count = String::from_utf8(response.body().to_vec()).unwrap();
+        if count != "0" {
+            break;
+        }
+    }
+    assert_eq!(count, "1");
+
+    let letter = auditor.read_request().await;
+    assert_eq!(letter.head().header("history"), Some("true"));
+    let letter: Value = serde_json::from_slice(letter.body()).unwrap();
+    assert_eq!(letter["event"], "test:order");
+    assert_eq!(letter["listener"], "printer");
+    assert_eq!(letter["body"], "lost");
+}
@@ -1,4 +1,9 @@
+#![allow(dead_code)]
+
+use camelot::tls::rustls::ClientConfig;
 use camelot::{ReadHalfClient, WriteHalfClient};
+use std::path::Path;
+use std::sync::Arc;
 use tokio::net::TcpStream;
 
 pub struct TestClient {
@@ -9,13 +14,38 @@ pub struct TestClient {
 
 impl TestClient {
     pub async fn new(name: &str) -> Self {
+        Self::connect("localhost:1237", name).await
+    }
+
+    pub async fn connect(addr: &str, name: &str) -> Self {
         let (reader, writer) = camelot::split(
-            TcpStream::connect("localhost:1237")
+            TcpStream::connect(addr)
                 .await
                 .expect("Could not connect"),
             name
         ).await;
         
+        Self::from_halves(reader, writer)
+    }
+
+    pub async fn connect_tls(addr: &str, name: &str, config: Arc<ClientConfig>) -> Self {
+        let stream = camelot::tls::connect(addr, "localhost", config)
+            .await
+            .expect("Could not connect");
+        let (reader, writer) = camelot::split(stream, name).await;
+
+        Self::from_halves(reader, writer)
+    }
+
+    #[cfg(unix)]
+    pub async fn connect_unix(path: &Path, name: &str) -> Self {
+        let stream = tokio::net::UnixStream::connect(path).await.expect("Could not connect");
+        let (reader, writer) = camelot::split(stream, name).await;
+
+        Self::from_halves(reader, writer)
+    }
+
+    fn from_halves(reader: ReadHalfClient, writer: WriteHalfClient) -> Self {
         Self {
             reader,
             writer,
@@ -26,7 +56,7 @@ impl TestClient {
 
 impl TestClient {
     
-    pub async fn establish_connection(&mut self) -> trtcp::Response {
+    pub async fn establish_connection(&mut self) -> trtcp::Response<'_> {
         let request = trtcp::Request::new(
             trtcp::Head::new_with_version(self.reader.name()),
             trtcp::Action::new(trtcp::ActionType::Connect, "", ""),
@@ -48,7 +78,7 @@ impl TestClient {
         self.writer.write(request).await.unwrap();
     }
 
-    pub async fn create_event(&mut self, event: &str) -> trtcp::Response {
+    pub async fn create_event(&mut self, event: &str) -> trtcp::Response<'_> {
         let request = trtcp::Request::new(
             trtcp::Head::new(trtcp::Version::actual(), self.reader.name()),
             trtcp::Action::new(trtcp::ActionType::Create, "test", event),
@@ -60,9 +90,21 @@ impl TestClient {
         self.reader.read(&mut self.buff).await.unwrap()
     }
 
-    pub async fn listen_event(&mut self, event: &str) -> trtcp::Response {
-        let request = trtcp::Request::new(
+    pub async fn listen_event(&mut self, event: &str) -> trtcp::Response<'_> {
+        self.listen_event_with_headers(event, &[]).await
+    }
+
+    pub async fn listen_event_with_headers(
+        &mut self,
+        event: &str,
+        headers: &[(&str, &str)],
+    ) -> trtcp::Response<'_> {
+        let head = headers.iter().fold(
             trtcp::Head::new(trtcp::Version::actual(), self.reader.name()),
+            |head, (key, value)| head.with_header(key, value),
+        );
+        let request = trtcp::Request::new(
+            head,
             trtcp::Action::new(trtcp::ActionType::Listen, "test", event),
             "".as_bytes(),
         );
@@ -72,11 +114,31 @@ impl TestClient {
         self.reader.read(&mut self.buff).await.unwrap()
     }
     
-    pub async fn read_response(&mut self) -> trtcp::Response {
+    /// Sends any request, `event` being `module:id`, and reads its response
+    pub async fn request(
+        &mut self,
+        r#type: trtcp::ActionType,
+        event: &str,
+        headers: &[(&str, &str)],
+        body: &[u8],
+    ) -> trtcp::Response<'_> {
+        let (module, id) = event.split_once(':').unwrap_or(("", ""));
+        let head = headers.iter().fold(
+            trtcp::Head::new(trtcp::Version::actual(), self.reader.name()),
+            |head, (key, value)| head.with_header(key, value),
+        );
+        let request = trtcp::Request::new(head, trtcp::Action::new(r#type, module, id), body);
+
+        self.writer.write(request).await.unwrap();
+
+        self.reader.read(&mut self.buff).await.unwrap()
+    }
+
+    pub async fn read_response(&mut self) -> trtcp::Response<'_> {
         self.reader.read(&mut self.buff).await.unwrap()
     }
     
-    pub async fn read_request(&mut self) -> trtcp::Request {
+    pub async fn read_request(&mut self) -> trtcp::Request<'_> {
         self.reader.read(&mut self.buff).await.unwrap()
     }
 }
\ No newline at end of file
@@ -0,0 +1,131 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use serde_json::{json, Value};
+use server::TestServer;
+use std::time::Duration;
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::TcpStream;
+use trtcp::StatusType;
+
+const HTTP_ADDR: &str = "127.0.0.1:1249";
+const AUTHORIZATION: &str = "Authorization: Bearer s3cret\r\n";
+
+async fn http(method: &str, path: &str, body: &str) -> (u16, String) {
+    http_to(HTTP_ADDR, AUTHORIZATION, method, path, body).await
+}
+
+async fn http_to(addr: &str, headers: &str, method: &str, path: &str, body: &str) -> (u16, String) {
+    let mut stream = None;
+    for _ in 0..50 {
+        if let Ok(s) = TcpStream::connect(addr).await {
+            stream = Some(s);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    let mut stream = stream.expect("Could not connect to the http api");
+
+    let request = format!(
+        "{} {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n{}Content-Length: {}\r\n\r\n{}",
+        method,
+        path,
+        headers,
+        body.len(),
+        body
+    );
+    stream.write_all(request.as_bytes()).await.unwrap();
+
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    let status = response[9..12].parse().unwrap();
+    let body = response.split_once("\r\n\r\n").unwrap().1.to_string();
+
+    (status, body)
+}
+
+fn as_json(body: &str) -> Value {
+    serde_json::from_str(body).unwrap()
+}
+
+#[tokio::test]
+async fn http_admin_api() {
+    let server = TestServer::start(1248, &["--http", HTTP_ADDR, "--http-token", "webhook=s3cret"]).await;
+
+    // The caller comes from the token, a request can't pick it
+    assert_eq!(http_to(HTTP_ADDR, "", "GET", "/events", "").await.0, 401);
+    let forged = "Authorization: Bearer wrong\r\nX-Caller: webhook\r\n";
+    assert_eq!(http_to(HTTP_ADDR, forged, "PUT", "/events/test/order", "").await.0, 401);
+
+    assert_eq!(http("PUT", "/events/test/order", "").await.0, 200);
+    assert_eq!(http("PUT", "/events/test/order", "").await.0, 409);
+
+    let mut terminal = Client::connect("localhost:1248", "terminal").await;
+    assert_eq!(*terminal.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*terminal.listen_event("order").await.status().r#type(), StatusType::OK);
+
+    let (status, body) = http("GET", "/clients", "").await;
+    assert_eq!(status, 200);
+    assert_eq!(as_json(&body), json!(["terminal"]));
+
+    let (status, body) = http("GET", "/events", "").await;
+    assert_eq!(status, 200);
+    let events = as_json(&body);
+    let events = events.as_array().unwrap();
+    let order = events.iter().find(|e| e["event"] == "test:order").unwrap();
+    assert_eq!(order["creator"], "webhook");
+    assert_eq!(order["listeners"][0]["name"], "terminal");
+
+    assert_eq!(http("POST", "/events/test/order", "paid").await.0, 200);
+    let callback = terminal.read_request().await;
+    assert_eq!(callback.head().caller(), "webhook");
+    assert_eq!(*callback.body(), "paid".as_bytes());
+
+    assert_eq!(http("POST", "/events/test/missing", "paid").await.0, 404);
+
+    // Only the creator or an admin can delete an event, as on trtcp
+    assert_eq!(*terminal.create_event("till").await.status().r#type(), StatusType::OK);
+    assert_eq!(http("DELETE", "/events/test/till", "").await.0, 403);
+
+    assert_eq!(http("DELETE", "/events/test/order", "").await.0, 200);
+    assert_eq!(http("GET", "/events/test/order", "").await.0, 404);
+    assert_eq!(http("DELETE", "/events/test/order", "").await.0, 404);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn metrics_endpoint() {
+    const METRICS_ADDR: &str = "127.0.0.1:1251";
+    let server = TestServer::start(1250, &["--metrics", METRICS_ADDR]).await;
+
+    let mut client = Client::connect("localhost:1250", "client").await;
+    assert_eq!(*client.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*client.create_event("metrics").await.status().r#type(), StatusType::OK);
+    assert_eq!(*client.listen_event("metrics").await.status().r#type(), StatusType::OK);
+
+    client.invoke_event("metrics", "Hello".as_bytes()).await;
+    client.read_request().await;
+    assert_eq!(*client.read_response().await.status().r#type(), StatusType::OK);
+
+    let (status, body) = http_to(METRICS_ADDR, "", "GET", "/metrics", "").await;
+    assert_eq!(status, 200);
+
+    for line in [
+        "camelot_connected_clients 1",
+        "camelot_requests_total{action=\"create\",status=\"OK\"} 1",
+        "camelot_requests_total{action=\"invoke\",status=\"OK\"} 1",
+        "camelot_callbacks_sent_total 1",
+        "camelot_invoke_fan_out_count 1",
+        "camelot_invoke_delivery_seconds_count 1",
+    ] {
+        assert!(body.lines().any(|l| l == line), "missing {} in\n{}", line, body);
+    }
+
+    // The admin API isn't served by the metrics listener
+    assert_eq!(http_to(METRICS_ADDR, "", "GET", "/events", "").await.0, 404);
+
+    server.stop().await;
+}
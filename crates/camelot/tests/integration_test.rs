@@ -2,10 +2,11 @@ mod client;
 mod server;
 
 use client::TestClient as Client;
+use trtcp::ActionType;
 
 #[tokio::test]
 async fn call_events() {
-    server::start_server().await;
+    let server = server::TestServer::start(1237, &[]).await;
 
     let test = tokio::spawn(async {
         let mut client1 = Client::new("client1").await;
@@ -35,11 +36,270 @@ async fn call_events() {
         check_response(&client1.read_response().await);
     }).await;
 
-    server::stop_server().await;
+    server.stop().await;
 
     assert!(test.is_ok());
 }
 
+#[tokio::test]
+async fn invoke_delivery_options() {
+    let addr = "localhost:1255";
+    let _server = server::TestServer::start(1255, &[]).await;
+
+    let mut client1 = Client::connect(addr, "client1").await;
+    let mut client2 = Client::connect(addr, "client2").await;
+    let mut client3 = Client::connect(addr, "client3").await;
+
+    check_response(&client1.establish_connection().await);
+    check_response(&client2.establish_connection().await);
+    check_response(&client3.establish_connection().await);
+
+    check_response(&client1.create_event("news").await);
+    check_response(&client1.listen_event("news").await);
+    check_response(&client2.listen_event("news").await);
+
+    // The caller doesn't get its own callback, so the next frame is the response
+    let response = client1
+        .request(ActionType::Invoke, "test:news", &[("exclude-self", "true")], b"first")
+        .await;
+    check_response(&response);
+
+    let response = client1
+        .request(ActionType::Invoke, "test:news", &[("to", "client2")], b"second")
+        .await;
+    check_response(&response);
+
+    // client3 isn't subscribed but it's connected
+    let response = client1
+        .request(ActionType::Invoke, "test:news", &[("to", "client3")], b"direct")
+        .await;
+    check_response(&response);
+
+    let headers = [("broadcast", "true"), ("exclude-self", "true")];
+    let response = client1
+        .request(ActionType::Invoke, "test:news", &headers, b"third")
+        .await;
+    check_response(&response);
+
+    check_callback(&client2.read_request().await, b"first");
+    check_callback(&client2.read_request().await, b"second");
+    check_callback(&client2.read_request().await, b"third");
+    check_callback(&client3.read_request().await, b"direct");
+    check_callback(&client3.read_request().await, b"third");
+}
+
+#[tokio::test]
+async fn retained_values() {
+    let addr = "localhost:1256";
+    let _server = server::TestServer::start(1256, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    check_response(&register.establish_connection().await);
+    check_response(
+        &register
+            .request(ActionType::Create, "test:basket", &[("retained", "true")], &[])
+            .await,
+    );
+
+    register.invoke_event("basket", b"two coffees").await;
+    check_response(&register.read_response().await);
+
+    // Targeted invokes aren't retained
+    let response = register
+        .request(ActionType::Invoke, "test:basket", &[("to", "nobody")], b"for nobody")
+        .await;
+    check_response(&response);
+
+    let mut display = Client::connect(addr, "display").await;
+    check_response(&display.establish_connection().await);
+    check_response(&display.listen_event("basket").await);
+
+    let callback = display.read_request().await;
+    check_callback(&callback, b"two coffees");
+    assert_eq!(callback.head().caller(), "register");
+    assert_eq!(callback.head().header("retained"), Some("true"));
+
+    let response = display.request(ActionType::Clear, "test:basket", &[], &[]).await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+    check_response(&register.request(ActionType::Clear, "test:basket", &[], &[]).await);
+
+    // Nothing is retained anymore, so the first callback is the next invoke
+    let mut kitchen = Client::connect(addr, "kitchen").await;
+    check_response(&kitchen.establish_connection().await);
+    check_response(&kitchen.listen_event("basket").await);
+
+    register.invoke_event("basket", b"three coffees").await;
+    check_response(&register.read_response().await);
+
+    let callback = kitchen.read_request().await;
+    check_callback(&callback, b"three coffees");
+    assert_eq!(callback.head().header("retained"), None);
+
+    // Listeners whose filter the retained invoke doesn't pass don't get it
+    let mut bar = Client::connect(addr, "bar").await;
+    check_response(&bar.establish_connection().await);
+    let response = bar
+        .listen_event_with_headers("basket", &[("filter", "header:channel == bar")])
+        .await;
+    check_response(&response);
+
+    let response = register
+        .request(ActionType::Invoke, "test:basket", &[("channel", "bar")], b"one tea")
+        .await;
+    check_response(&response);
+
+    let callback = bar.read_request().await;
+    check_callback(&callback, b"one tea");
+    assert_eq!(callback.head().header("retained"), None);
+
+    // Updates the invoker doesn't get back are still the state of the event
+    check_response(&register.listen_event("basket").await);
+    let callback = register.read_request().await;
+    check_callback(&callback, b"one tea");
+    assert_eq!(callback.head().header("retained"), Some("true"));
+
+    let response = register
+        .request(ActionType::Invoke, "test:basket", &[("exclude-self", "true")], b"four coffees")
+        .await;
+    check_response(&response);
+
+    let mut screen = Client::connect(addr, "screen").await;
+    check_response(&screen.establish_connection().await);
+    check_response(&screen.listen_event("basket").await);
+
+    let callback = screen.read_request().await;
+    check_callback(&callback, b"four coffees");
+    assert_eq!(callback.head().header("retained"), Some("true"));
+}
+
+#[tokio::test]
+async fn event_history() {
+    let addr = "localhost:1257";
+    let _server = server::TestServer::start(1257, &["--max-retention", "100"]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    check_response(&register.establish_connection().await);
+    let response = register
+        .request(ActionType::Create, "test:journal", &[("retention", "101")], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidRequest);
+    check_response(
+        &register
+            .request(ActionType::Create, "test:receipt", &[("retention", "3")], &[])
+            .await,
+    );
+
+    for receipt in ["1", "2", "3", "4", "5"] {
+        register.invoke_event("receipt", receipt.as_bytes()).await;
+        check_response(&register.read_response().await);
+    }
+
+    let mut printer = Client::connect(addr, "printer").await;
+    check_response(&printer.establish_connection().await);
+
+    // Only the last three are retained
+    let response = printer.request(ActionType::History, "test:receipt", &[], &[]).await;
+    check_response(&response);
+    assert_eq!(response.body_as_str().unwrap(), "3");
+    for (seq, receipt) in [("3", "3"), ("4", "4"), ("5", "5")] {
+        let callback = printer.read_request().await;
+        check_callback(&callback, receipt.as_bytes());
+        assert_eq!(callback.head().header("seq"), Some(seq));
+        assert_eq!(callback.head().caller(), "register");
+        assert!(callback.head().header("timestamp").is_some());
+    }
+
+    let response = printer
+        .request(ActionType::History, "test:receipt", &[("last", "1")], &[])
+        .await;
+    assert_eq!(response.body_as_str().unwrap(), "1");
+    check_callback(&printer.read_request().await, b"5");
+
+    let response = printer
+        .request(ActionType::History, "test:receipt", &[("since", "3")], &[])
+        .await;
+    assert_eq!(response.body_as_str().unwrap(), "2");
+    check_callback(&printer.read_request().await, b"4");
+    check_callback(&printer.read_request().await, b"5");
+}
+
+#[tokio::test]
+async fn delete_events() {
+    let addr = "localhost:1258";
+    let _server = server::TestServer::start(1258, &["--admin", "boss"]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    let mut display = Client::connect(addr, "display").await;
+    let mut boss = Client::connect(addr, "boss").await;
+    check_response(&register.establish_connection().await);
+    check_response(&display.establish_connection().await);
+    check_response(&boss.establish_connection().await);
+
+    check_response(&register.create_event("basket").await);
+    check_response(&register.create_event("receipt").await);
+    check_response(&display.listen_event("basket").await);
+
+    let response = display.request(ActionType::Delete, "test:basket", &[], &[]).await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+
+    check_response(&register.request(ActionType::Delete, "test:basket", &[], &[]).await);
+
+    let callback = display.read_request().await;
+    assert_eq!(callback.head().caller(), "camelot");
+    assert_eq!(callback.head().header("deleted"), Some("true"));
+    assert_eq!(*callback.action().id(), "basket");
+
+    let response = display.listen_event("basket").await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::EventNotFound);
+
+    // Admins can delete the events of others, but not the system ones
+    check_response(&boss.request(ActionType::Delete, "test:receipt", &[], &[]).await);
+    let response = boss
+        .request(ActionType::Delete, "camelot:deadLetter", &[], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+}
+
+#[tokio::test]
+async fn pattern_subscriptions() {
+    let addr = "localhost:1259";
+    let _server = server::TestServer::start(1259, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    let mut audit = Client::connect(addr, "audit").await;
+    check_response(&register.establish_connection().await);
+    check_response(&audit.establish_connection().await);
+
+    check_response(&register.create_event("refund").await);
+    check_response(&audit.request(ActionType::Listen, "test:*", &[], &[]).await);
+    check_response(&audit.request(ActionType::Listen, "*:order", &[], &[]).await);
+    check_response(&register.create_event("order").await);
+
+    // order matches both patterns but it's only delivered once
+    register.invoke_event("refund", b"refund 1").await;
+    check_response(&register.read_response().await);
+    register.invoke_event("order", b"order 1").await;
+    check_response(&register.read_response().await);
+
+    check_callback(&audit.read_request().await, b"refund 1");
+    check_callback(&audit.read_request().await, b"order 1");
+
+    check_response(&audit.request(ActionType::Leave, "test:*", &[], &[]).await);
+    let response = audit.request(ActionType::Leave, "test:*", &[], &[]).await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::ListenerNotFound);
+
+    register.invoke_event("refund", b"refund 2").await;
+    check_response(&register.read_response().await);
+    check_response(&register.create_event("receipt").await);
+    register.invoke_event("receipt", b"receipt 1").await;
+    check_response(&register.read_response().await);
+    register.invoke_event("order", b"order 2").await;
+    check_response(&register.read_response().await);
+
+    // *:order is still there, so the order is the next callback
+    check_callback(&audit.read_request().await, b"order 2");
+}
+
 fn check_response(response: &trtcp::Response) {
     if *response.status().r#type() != trtcp::StatusType::OK {
         panic!("Response status is not OK: {:?}", response);
@@ -50,4 +310,456 @@ fn check_callback(request: &trtcp::Request, body: &[u8]) {
     if *request.body() != body {
         panic!("Response body is not equal to expected: {:?} != {:?}", request.body(), body);
     }
-}
\ No newline at end of file
+}
+#[tokio::test]
+async fn invoke_delivery_report() {
+    let addr = "localhost:1262";
+    let _server = server::TestServer::start(1262, &[]).await;
+
+    let mut client1 = Client::connect(addr, "client1").await;
+    let mut client2 = Client::connect(addr, "client2").await;
+    let mut client3 = Client::connect(addr, "client3").await;
+
+    check_response(&client1.establish_connection().await);
+    check_response(&client2.establish_connection().await);
+    check_response(&client3.establish_connection().await);
+
+    check_response(&client1.create_event("report").await);
+    check_response(&client2.listen_event("report").await);
+    check_response(&client3.listen_event("report").await);
+
+    // client3 is still a listener but no longer connected
+    drop(client3);
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+
+    let response = client1
+        .request(ActionType::Invoke, "test:report", &[("report", "true")], b"hello")
+        .await;
+    check_response(&response);
+
+    let report: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(report["matched"], 2);
+    assert_eq!(report["delivered"], serde_json::json!(["client2"]));
+    assert_eq!(report["queued"], serde_json::json!([]));
+    assert_eq!(report["failed"][0]["listener"], "client3");
+    assert_eq!(report["failed"][0]["error"], "Not connected");
+
+    check_callback(&client2.read_request().await, b"hello");
+
+    // Without the header the body stays empty
+    let response = client1.request(ActionType::Invoke, "test:report", &[], b"again").await;
+    check_response(&response);
+    assert!(response.body().is_empty());
+}
+
+#[tokio::test]
+async fn filtered_subscriptions() {
+    let addr = "localhost:1264";
+    let _server = server::TestServer::start(1264, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    let mut terminal1 = Client::connect(addr, "terminal1").await;
+    let mut terminal2 = Client::connect(addr, "terminal2").await;
+    let mut audit = Client::connect(addr, "audit").await;
+    check_response(&register.establish_connection().await);
+    check_response(&terminal1.establish_connection().await);
+    check_response(&terminal2.establish_connection().await);
+    check_response(&audit.establish_connection().await);
+
+    check_response(&register.create_event("order").await);
+    check_response(&terminal1.listen_event_with_headers("order", &[("filter", "till == T1")]).await);
+    let filter = r#"till == "T2" && total >= 10 && header:channel == pos"#;
+    check_response(&terminal2.listen_event_with_headers("order", &[("filter", filter)]).await);
+    check_response(&audit.listen_event("order").await);
+
+    let response = audit.listen_event_with_headers("order", &[("filter", "till T1")]).await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidRequest);
+
+    let orders = [
+        (r#"{"till":"T1","total":5}"#, "pos"),
+        (r#"{"till":"T2","total":5}"#, "pos"),
+        (r#"{"till":"T2","total":15}"#, "web"),
+        (r#"{"till":"T2","total":20}"#, "pos"),
+    ];
+    for (order, channel) in orders {
+        let response = register
+            .request(ActionType::Invoke, "test:order", &[("channel", channel)], order.as_bytes())
+            .await;
+        check_response(&response);
+    }
+
+    for (order, _) in orders {
+        check_callback(&audit.read_request().await, order.as_bytes());
+    }
+    check_callback(&terminal1.read_request().await, orders[0].0.as_bytes());
+    check_callback(&terminal2.read_request().await, orders[3].0.as_bytes());
+}
+
+#[tokio::test]
+async fn system_events() {
+    let addr = "localhost:1265";
+    let _server = server::TestServer::start(1265, &[]).await;
+
+    let mut manager = Client::connect(addr, "manager").await;
+    check_response(&manager.establish_connection().await);
+    for event in ["clientConnected", "clientDisconnected", "eventCreated", "eventDeleted"] {
+        let response = manager
+            .request(ActionType::Listen, &format!("camelot:{}", event), &[], &[])
+            .await;
+        check_response(&response);
+    }
+
+    let response = manager
+        .request(ActionType::Invoke, "camelot:clientConnected", &[], b"fake")
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+
+    // Nobody can pass for the broker, on either kind of connection
+    let mut impostor = Client::connect(addr, "camelot").await;
+    let response = impostor.establish_connection().await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+    let mut impostor = Client::connect(addr, "camelot").await;
+    let response = impostor.request(ActionType::Invoke, "test:payment", &[], b"fake").await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+
+    let mut plugin = Client::connect(addr, "plugin").await;
+    check_response(&plugin.establish_connection().await);
+    check_response(&plugin.create_event("payment").await);
+    check_response(&plugin.request(ActionType::Delete, "test:payment", &[], &[]).await);
+    drop(plugin);
+
+    let expected = [
+        ("clientConnected", serde_json::json!({"caller": "plugin", "reason": "connected"})),
+        (
+            "eventCreated",
+            serde_json::json!({"caller": "plugin", "reason": "created", "event": "test:payment"}),
+        ),
+        (
+            "eventDeleted",
+            serde_json::json!({"caller": "plugin", "reason": "deleted", "event": "test:payment"}),
+        ),
+        ("clientDisconnected", serde_json::json!({"caller": "plugin", "reason": "closed"})),
+    ];
+    for (event, body) in expected {
+        let callback = manager.read_request().await;
+        assert_eq!(callback.head().caller(), "camelot");
+        assert_eq!(*callback.action().id(), event);
+        assert_eq!(serde_json::from_slice::<serde_json::Value>(callback.body()).unwrap(), body);
+    }
+}
+
+#[tokio::test]
+async fn last_wills() {
+    let addr = "localhost:1266";
+    let _server = server::TestServer::start(1266, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    check_response(&register.establish_connection().await);
+    check_response(&register.create_event("terminalLost").await);
+    check_response(&register.listen_event("terminalLost").await);
+
+    let will = [("will", "test:terminalLost")];
+
+    // The connection ends without a disconnect, so the will is invoked
+    let mut terminal1 = Client::connect(addr, "terminal1").await;
+    check_response(&terminal1.request(ActionType::Connect, ":", &will, b"terminal1 lost").await);
+    drop(terminal1);
+
+    let callback = register.read_request().await;
+    assert_eq!(callback.head().caller(), "terminal1");
+    check_callback(&callback, b"terminal1 lost");
+
+    // A clean disconnect discards it
+    let mut terminal2 = Client::connect(addr, "terminal2").await;
+    check_response(&terminal2.request(ActionType::Connect, ":", &will, b"terminal2 lost").await);
+    check_response(&terminal2.request(ActionType::Disconnect, ":", &[], &[]).await);
+    drop(terminal2);
+
+    tokio::time::sleep(std::time::Duration::from_millis(100)).await;
+    register.invoke_event("terminalLost", b"nothing else").await;
+    check_callback(&register.read_request().await, b"nothing else");
+    check_response(&register.read_response().await);
+
+    let mut terminal3 = Client::connect(addr, "terminal3").await;
+    let response = terminal3
+        .request(ActionType::Connect, ":", &[("will", "terminalLost")], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidRequest);
+}
+
+#[tokio::test]
+async fn last_will_on_timeout() {
+    let addr = "localhost:1267";
+    let _server = server::TestServer::start(1267, &["--idle-timeout-ms", "300"]).await;
+
+    let mut terminal = Client::connect(addr, "terminal").await;
+    let will = [("will", "test:terminalLost")];
+    check_response(&terminal.request(ActionType::Connect, ":", &will, b"terminal timed out").await);
+
+    let mut register = Client::connect(addr, "register").await;
+    check_response(&register.establish_connection().await);
+    check_response(&register.create_event("terminalLost").await);
+    check_response(&register.listen_event("terminalLost").await);
+
+    // The register stays active longer than the terminal
+    tokio::time::sleep(std::time::Duration::from_millis(150)).await;
+    check_response(&register.create_event("keepAlive").await);
+
+    let callback = register.read_request().await;
+    assert_eq!(callback.head().caller(), "terminal");
+    check_callback(&callback, b"terminal timed out");
+}
+
+#[tokio::test]
+async fn introspection() {
+    let addr = "localhost:1268";
+    let _server = server::TestServer::start(1268, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    let mut manager = Client::connect(addr, "manager").await;
+    check_response(&register.establish_connection().await);
+    check_response(&manager.establish_connection().await);
+
+    check_response(&register.create_event("order").await);
+    let response = register
+        .request(ActionType::Create, "test:receipt", &[("retained", "true")], &[])
+        .await;
+    check_response(&response);
+    check_response(&manager.listen_event_with_headers("order", &[("group", "ui")]).await);
+    check_response(&manager.request(ActionType::Listen, "test:*", &[], &[]).await);
+
+    let response = manager.request(ActionType::Events, "test:", &[], &[]).await;
+    check_response(&response);
+    let events: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(events, serde_json::json!(["test:order", "test:receipt"]));
+
+    let response = manager.request(ActionType::Events, ":", &[], &[]).await;
+    let events: Vec<String> = serde_json::from_slice(response.body()).unwrap();
+    assert!(events.contains(&"camelot:clientConnected".to_string()));
+    assert!(events.contains(&"test:order".to_string()));
+
+    let response = manager.request(ActionType::Describe, "test:receipt", &[], &[]).await;
+    check_response(&response);
+    let description: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(description["event"], "test:receipt");
+    assert_eq!(description["creator"], "register");
+    assert_eq!(description["listeners"], 1);
+    assert_eq!(description["retained"], true);
+    assert!(description["created_at"].as_u64().unwrap() > 0);
+
+    let response = manager.request(ActionType::Describe, "test:missing", &[], &[]).await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::EventNotFound);
+
+    let response = manager.request(ActionType::Subscriptions, ":", &[], &[]).await;
+    check_response(&response);
+    let subscriptions: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(subscriptions.as_array().unwrap().len(), 2);
+    assert_eq!(subscriptions[0]["event"], "test:*");
+    assert_eq!(subscriptions[1]["event"], "test:order");
+    assert_eq!(subscriptions[1]["group"], "ui");
+}
+
+#[tokio::test]
+async fn event_schemas() {
+    let addr = "localhost:1269";
+    let _server = server::TestServer::start(1269, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    check_response(&register.establish_connection().await);
+
+    let schema = serde_json::json!({
+        "type": "object",
+        "properties": {
+            "till": {"type": "string"},
+            "lines": {"type": "array", "items": {"type": "object", "required": ["sku"]}}
+        },
+        "required": ["till"]
+    });
+    let response = register
+        .request(ActionType::Create, "test:order", &[], schema.to_string().as_bytes())
+        .await;
+    check_response(&response);
+
+    let response = register
+        .request(ActionType::Create, "test:broken", &[], br#"{"type": 3}"#)
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidRequest);
+
+    let valid = br#"{"till": "T1", "lines": [{"sku": "A"}]}"#;
+    let response = register.request(ActionType::Invoke, "test:order", &[], valid).await;
+    check_response(&response);
+
+    let invalid = br#"{"till": "T1", "lines": [{"sku": "A"}, {"qty": 2}]}"#;
+    let response = register.request(ActionType::Invoke, "test:order", &[], invalid).await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidPayload);
+    let violation: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(violation["path"], "/lines/1");
+    assert!(violation["error"].as_str().unwrap().contains("sku"));
+
+    let response = register.request(ActionType::Invoke, "test:order", &[], b"not json").await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidPayload);
+
+    let response = register.request(ActionType::Request, "test:order", &[], b"not json").await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidPayload);
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("delay", "10")], br#"{"lines": []}"#)
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidPayload);
+
+    let response = register.request(ActionType::Describe, "test:order", &[], &[]).await;
+    check_response(&response);
+    let description: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(description["schema"], schema);
+}
+
+#[tokio::test]
+async fn sequence_numbers() {
+    let addr = "localhost:1270";
+    let _server = server::TestServer::start(1270, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    check_response(&register.establish_connection().await);
+    check_response(&register.create_event("order").await);
+
+    let mut displays = Vec::new();
+    for name in ["display1", "display2"] {
+        let mut display = Client::connect(addr, name).await;
+        check_response(&display.establish_connection().await);
+        check_response(&display.listen_event("order").await);
+        displays.push(display);
+    }
+
+    // Two terminals invoke at the same time
+    let invokers: Vec<_> = ["terminal1", "terminal2"]
+        .into_iter()
+        .map(|name| {
+            tokio::spawn(async move {
+                let mut terminal = Client::connect(addr, name).await;
+                check_response(&terminal.establish_connection().await);
+                for i in 0..50 {
+                    let body = format!("{} {}", name, i);
+                    terminal.invoke_event("order", body.as_bytes()).await;
+                    check_response(&terminal.read_response().await);
+                }
+            })
+        })
+        .collect();
+
+    let mut orders = Vec::new();
+    for display in displays.iter_mut() {
+        let mut received = Vec::new();
+        for seq in 1..=100u64 {
+            let callback = display.read_request().await;
+            assert_eq!(callback.head().header("seq"), Some(seq.to_string().as_str()));
+            received.push(callback.body().to_vec());
+        }
+        orders.push(received);
+    }
+
+    // Both displays see the invokes in the same order
+    assert_eq!(orders[0], orders[1]);
+
+    for invoker in invokers {
+        invoker.await.unwrap();
+    }
+}
+
+#[tokio::test]
+async fn scheduled_invokes() {
+    let addr = "localhost:1271";
+    let _server = server::TestServer::start(1271, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    let mut display = Client::connect(addr, "display").await;
+    let mut manager = Client::connect(addr, "manager").await;
+    check_response(&register.establish_connection().await);
+    check_response(&display.establish_connection().await);
+    check_response(&manager.establish_connection().await);
+    check_response(&register.create_event("order").await);
+    check_response(&display.listen_event("order").await);
+
+    // Clients can't pass their invokes off as scheduled
+    let response = register
+        .request(ActionType::Invoke, "test:order", &[("schedule-id", "1")], b"forged")
+        .await;
+    check_response(&response);
+    let callback = display.read_request().await;
+    check_callback(&callback, b"forged");
+    assert_eq!(callback.head().header("schedule-id"), None);
+
+    // Neither through a temporal connection
+    let mut temporal = Client::connect(addr, "temporal").await;
+    let response = temporal
+        .request(ActionType::Invoke, "test:order", &[("schedule-id", "1")], b"forged once")
+        .await;
+    check_response(&response);
+    let callback = display.read_request().await;
+    check_callback(&callback, b"forged once");
+    assert_eq!(callback.head().header("schedule-id"), None);
+
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("delay", "200")], b"delayed")
+        .await;
+    check_response(&response);
+    let delayed_id = String::from_utf8(response.body().to_vec()).unwrap();
+
+    let callback = display.read_request().await;
+    assert_eq!(callback.head().caller(), "register");
+    assert_eq!(callback.head().header("schedule-id"), Some(delayed_id.as_str()));
+    check_callback(&callback, b"delayed");
+
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("cron", "* * * * * *")], b"tick")
+        .await;
+    check_response(&response);
+    let cron_id = String::from_utf8(response.body().to_vec()).unwrap();
+
+    for _ in 0..2 {
+        let callback = display.read_request().await;
+        assert_eq!(callback.head().header("schedule-id"), Some(cron_id.as_str()));
+        check_callback(&callback, b"tick");
+    }
+
+    // The delayed invoke is gone after it fired
+    let response = register.request(ActionType::Schedules, ":", &[], &[]).await;
+    check_response(&response);
+    let schedules: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(schedules.as_array().unwrap().len(), 1);
+    assert_eq!(schedules[0]["id"].to_string(), cron_id);
+    assert_eq!(schedules[0]["event"], "test:order");
+    assert_eq!(schedules[0]["cron"], "* * * * * *");
+    assert_eq!(schedules[0]["body"], "tick");
+
+    let response = manager.request(ActionType::Schedules, ":", &[], &[]).await;
+    assert_eq!(response.body(), b"[]");
+
+    let response = manager
+        .request(ActionType::Cancel, ":", &[("schedule-id", &cron_id)], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::Forbidden);
+
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("cron", "every day")], b"")
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidRequest);
+
+    let delay = u64::MAX.to_string();
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("delay", &delay)], b"")
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::InvalidRequest);
+
+    let response = register
+        .request(ActionType::Schedule, "test:missing", &[("delay", "10")], b"")
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::EventNotFound);
+
+    let response = register
+        .request(ActionType::Cancel, ":", &[("schedule-id", &cron_id)], &[])
+        .await;
+    check_response(&response);
+    let response = register
+        .request(ActionType::Cancel, ":", &[("schedule-id", &cron_id)], &[])
+        .await;
+    assert_eq!(*response.status().r#type(), trtcp::StatusType::ScheduleNotFound);
+}
@@ -0,0 +1,91 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use serde_json::{json, Value};
+use server::TestServer;
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader, Lines};
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::TcpStream;
+use trtcp::StatusType;
+
+struct JsonClient {
+    lines: Lines<BufReader<OwnedReadHalf>>,
+    writer: OwnedWriteHalf,
+}
+
+impl JsonClient {
+    async fn connect(addr: &str) -> Self {
+        for _ in 0..50 {
+            if let Ok(stream) = TcpStream::connect(addr).await {
+                let (reader, writer) = stream.into_split();
+                return Self {
+                    lines: BufReader::new(reader).lines(),
+                    writer,
+                };
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+        panic!("Could not connect to {}", addr);
+    }
+
+    async fn send(&mut self, message: Value) {
+        let line = format!("{}\n", message);
+        self.writer.write_all(line.as_bytes()).await.unwrap();
+    }
+
+    async fn receive(&mut self) -> Value {
+        let line = self.lines.next_line().await.unwrap().expect("Connection closed");
+        serde_json::from_str(&line).unwrap()
+    }
+}
+
+#[tokio::test]
+async fn json_clients_map_onto_trtcp() {
+    let server = TestServer::start(1246, &["--json", "127.0.0.1:1247"]).await;
+
+    let mut ops = JsonClient::connect("127.0.0.1:1247").await;
+    let mut plugin = Client::connect("localhost:1246", "plugin").await;
+
+    ops.send(json!({"caller": "ops", "action": "connect"})).await;
+    assert_eq!(ops.receive().await, json!({"caller": "ops", "status": "OK", "body": ""}));
+    assert_eq!(*plugin.establish_connection().await.status().r#type(), StatusType::OK);
+
+    // A malformed line is answered and the connection stays open
+    ops.writer.write_all(b"not json\n").await.unwrap();
+    let response = ops.receive().await;
+    assert_eq!(response["status"], "InvalidRequest");
+    ops.send(json!({"action": "invoke", "event": "orderModified"})).await;
+    assert_eq!(ops.receive().await["status"], "InvalidRequest");
+    ops.send(json!({"action": "invoke", "event": "test:orderModified", "headers": {"to": "\u{1F}"}}))
+        .await;
+    assert_eq!(ops.receive().await["status"], "InvalidRequest");
+
+    ops.send(json!({"action": "create", "event": "test:orderModified"})).await;
+    assert_eq!(ops.receive().await["status"], "OK");
+
+    ops.send(json!({"action": "listen", "event": "test:orderModified"})).await;
+    assert_eq!(ops.receive().await["status"], "OK");
+    assert_eq!(*plugin.listen_event("orderModified").await.status().r#type(), StatusType::OK);
+
+    ops.send(json!({"action": "invoke", "event": "test:orderModified", "body": "{\"id\":7}"})).await;
+
+    assert_eq!(
+        ops.receive().await,
+        json!({
+            "caller": "ops",
+            "action": "callback",
+            "event": "test:orderModified",
+            "headers": {"seq": "1"},
+            "body": "{\"id\":7}"
+        })
+    );
+    assert_eq!(ops.receive().await["status"], "OK");
+    assert_eq!(*plugin.read_request().await.body(), "{\"id\":7}".as_bytes());
+
+    ops.send(json!({"action": "listen", "event": "test:missing"})).await;
+    assert_eq!(ops.receive().await["status"], "EventNotFound");
+
+    server.stop().await;
+}
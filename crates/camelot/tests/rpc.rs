@@ -0,0 +1,117 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use server::TestServer;
+use trtcp::{ActionType, StatusType};
+
+const ADDR: &str = "localhost:1260";
+
+#[tokio::test]
+async fn requests_are_answered_by_one_responder() {
+    let _server = TestServer::start(1260, &[]).await;
+
+    let mut register = Client::connect(ADDR, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("price").await.status().r#type(), StatusType::OK);
+
+    let response = register.request(ActionType::Request, "test:price", &[], b"sku-1").await;
+    assert_eq!(*response.status().r#type(), StatusType::ListenerNotFound);
+
+    let mut pricing = Client::connect(ADDR, "pricing").await;
+    assert_eq!(*pricing.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = pricing
+        .listen_event_with_headers("price", &[("responder", "true")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    let responder = tokio::spawn(async move {
+        let callback = pricing.read_request().await;
+        assert_eq!(callback.head().caller(), "register");
+        assert_eq!(*callback.body(), b"sku-1");
+        let request_id = callback.head().header("request-id").unwrap().to_string();
+
+        let response = pricing
+            .request(ActionType::Reply, "test:price", &[("request-id", &request_id)], b"4.50")
+            .await;
+        assert_eq!(*response.status().r#type(), StatusType::OK);
+
+        // The second request is never answered
+        pricing.read_request().await;
+        pricing
+    });
+
+    let response = register.request(ActionType::Request, "test:price", &[], b"sku-1").await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    assert_eq!(response.body(), b"4.50");
+
+    let response = register
+        .request(ActionType::Request, "test:price", &[("timeout", "100")], b"sku-2")
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::Timeout);
+
+    responder.await.unwrap();
+}
+
+#[tokio::test]
+async fn gather_invokes_collect_replies() {
+    let addr = "localhost:1261";
+    let _server = TestServer::start(1261, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("close").await.status().r#type(), StatusType::OK);
+
+    let mut terminals = Vec::new();
+    for name in ["terminal1", "terminal2", "terminal3"] {
+        let mut terminal = Client::connect(addr, name).await;
+        assert_eq!(*terminal.establish_connection().await.status().r#type(), StatusType::OK);
+        assert_eq!(*terminal.listen_event("close").await.status().r#type(), StatusType::OK);
+        terminals.push(terminal);
+    }
+
+    // terminal3 never replies
+    let silent = terminals.pop().unwrap();
+    let repliers: Vec<_> = terminals
+        .into_iter()
+        .map(|mut terminal| {
+            tokio::spawn(async move {
+                let callback = terminal.read_request().await;
+                let request_id = callback.head().header("request-id").unwrap().to_string();
+                let tabs = if callback.head().caller() == "register" { b"no tabs" } else { b"unknown" };
+
+                let response = terminal
+                    .request(ActionType::Reply, "test:close", &[("request-id", &request_id)], tabs)
+                    .await;
+                assert_eq!(*response.status().r#type(), StatusType::OK);
+            })
+        })
+        .collect();
+
+    let headers = [("gather", "true"), ("deadline", "300")];
+    let response = register.request(ActionType::Invoke, "test:close", &headers, b"closing").await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    let result: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    let mut replies: Vec<(String, String)> = result["replies"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|r| (r["caller"].as_str().unwrap().into(), r["body"].as_str().unwrap().into()))
+        .collect();
+    replies.sort();
+
+    assert_eq!(
+        replies,
+        vec![
+            ("terminal1".to_string(), "no tabs".to_string()),
+            ("terminal2".to_string(), "no tabs".to_string()),
+        ]
+    );
+    assert_eq!(result["missing"], serde_json::json!(["terminal3"]));
+
+    for replier in repliers {
+        replier.await.unwrap();
+    }
+    drop(silent);
+}
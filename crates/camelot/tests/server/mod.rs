@@ -1,22 +1,59 @@
-use std::sync::LazyLock;
+#![allow(dead_code)]
+
+use std::path::Path;
 use std::time::Duration;
-use tokio::process::Child;
-use tokio::process::Command;
-use tokio::sync::Mutex;
+use tokio::net::TcpStream;
+use tokio::process::{Child, Command};
+
+/// Broker process running for the duration of a test, it's killed when dropped
+pub struct TestServer {
+    child: Child,
+}
+
+impl TestServer {
+    pub async fn start(port: u16, args: &[&str]) -> Self {
+        let server = Self::spawn(port, args);
+
+        // Waiting until the broker accepts connections
+        for _ in 0..100 {
+            if TcpStream::connect(("localhost", port)).await.is_ok() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        server
+    }
 
-static SERVER: LazyLock<Mutex<Child>> = LazyLock::new(|| {
-    Mutex::new(
-        Command::new("../../target/debug/server")
+    /// Starts a broker that only listens on the unix socket of `path`
+    pub async fn start_unix(path: &Path, args: &[&str]) -> Self {
+        let mut unix_args = vec!["--no-tcp", "--unix", path.to_str().unwrap()];
+        unix_args.extend_from_slice(args);
+
+        let server = Self::spawn(0, &unix_args);
+
+        for _ in 0..100 {
+            if path.exists() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        server
+    }
+
+    fn spawn(port: u16, args: &[&str]) -> Self {
+        let child = Command::new(env!("CARGO_BIN_EXE_camelot"))
+            .arg(port.to_string())
+            .args(args)
+            .kill_on_drop(true)
             .spawn()
-            .expect("Could not start server"),
-    )
-});
+            .expect("Could not start server");
 
-pub async  fn start_server() {
-    let _ = SERVER.lock().await;
-    tokio::time::sleep(Duration::from_millis(100)).await;
-}
+        Self { child }
+    }
 
-pub async fn stop_server() {
-    SERVER.lock().await.kill().await.expect("Could not kill server");
+    pub async fn stop(mut self) {
+        self.child.kill().await.expect("Could not kill server");
+    }
 }
@@ -0,0 +1,159 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use server::TestServer;
+use std::time::Duration;
+use trtcp::{ActionType, StatusType};
+
+const ADDR: &str = "localhost:1252";
+
+#[tokio::test]
+async fn sqlite_store_survives_restarts() {
+    let dir = tempfile::tempdir().unwrap();
+    let database = dir.path().join("camelot.db");
+    let args = ["--sqlite", database.to_str().unwrap()];
+
+    let server = TestServer::start(1252, &args).await;
+
+    let mut register = Client::connect(ADDR, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("order").await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("refund").await.status().r#type(), StatusType::OK);
+
+    let mut terminal = Client::connect(ADDR, "terminal").await;
+    assert_eq!(*terminal.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = terminal
+        .listen_event_with_headers("order", &[("durable", "true")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    assert_eq!(*terminal.listen_event("refund").await.status().r#type(), StatusType::OK);
+
+    register.invoke_event("order", "opened".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    assert_eq!(terminal.read_request().await.head().header("seq"), Some("1"));
+
+    server.stop().await;
+    let _server = TestServer::start(1252, &args).await;
+
+    let mut register = Client::connect(ADDR, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(
+        *register.create_event("order").await.status().r#type(),
+        StatusType::EventAlreadyExists
+    );
+
+    // Only the durable subscription is still there
+    let mut terminal = Client::connect(ADDR, "terminal").await;
+    assert_eq!(*terminal.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(
+        *terminal.listen_event("order").await.status().r#type(),
+        StatusType::AlreadySubscribed
+    );
+    assert_eq!(*terminal.listen_event("refund").await.status().r#type(), StatusType::OK);
+
+    register.invoke_event("order", "paid".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+
+    let callback = terminal.read_request().await;
+    assert_eq!(callback.head().caller(), "register");
+    assert_eq!(*callback.body(), "paid".as_bytes());
+
+    // Sequence numbers keep growing after a restart
+    let seq: u64 = callback.head().header("seq").unwrap().parse().unwrap();
+    assert!(seq > 1);
+}
+
+#[tokio::test]
+async fn offline_durable_listener_gets_queued_callbacks() {
+    let addr = "localhost:1253";
+    let _server = TestServer::start(1253, &[]).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("order").await.status().r#type(), StatusType::OK);
+
+    let mut printer = Client::connect(addr, "printer").await;
+    assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = printer
+        .listen_event_with_headers("order", &[("durable", "true")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+
+    drop(printer);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+
+    for body in ["first", "second", "third"] {
+        register.invoke_event("order", body.as_bytes()).await;
+        assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+    }
+
+    let mut printer = Client::connect(addr, "printer").await;
+    assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+
+    register.invoke_event("order", "fourth".as_bytes()).await;
+    assert_eq!(*register.read_response().await.status().r#type(), StatusType::OK);
+
+    for body in ["first", "second", "third", "fourth"] {
+        assert_eq!(*printer.read_request().await.body(), body.as_bytes());
+    }
+}
+
+#[tokio::test]
+async fn schedules_survive_restarts() {
+    let addr = "localhost:1272";
+    let dir = tempfile::tempdir().unwrap();
+    let database = dir.path().join("camelot.db");
+    let args = ["--sqlite", database.to_str().unwrap()];
+
+    let server = TestServer::start(1272, &args).await;
+
+    let mut register = Client::connect(addr, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*register.create_event("order").await.status().r#type(), StatusType::OK);
+
+    let mut printer = Client::connect(addr, "printer").await;
+    assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = printer
+        .listen_event_with_headers("order", &[("durable", "true")])
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    drop(printer);
+
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("delay", "300")], b"later")
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    let delayed_id = String::from_utf8(response.body().to_vec()).unwrap();
+
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("cron", "0 0 1 1 *")], b"new year")
+        .await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    let cron_id = String::from_utf8(response.body().to_vec()).unwrap();
+
+    // The delayed invoke is due while the broker is down, it fires once it's back
+    server.stop().await;
+    tokio::time::sleep(Duration::from_millis(400)).await;
+    let _server = TestServer::start(1272, &args).await;
+
+    let mut printer = Client::connect(addr, "printer").await;
+    assert_eq!(*printer.establish_connection().await.status().r#type(), StatusType::OK);
+    let callback = printer.read_request().await;
+    assert_eq!(callback.head().header("schedule-id"), Some(delayed_id.as_str()));
+    assert_eq!(*callback.body(), "later".as_bytes());
+
+    let mut register = Client::connect(addr, "register").await;
+    assert_eq!(*register.establish_connection().await.status().r#type(), StatusType::OK);
+    let response = register.request(ActionType::Schedules, ":", &[], &[]).await;
+    let schedules: serde_json::Value = serde_json::from_slice(response.body()).unwrap();
+    assert_eq!(schedules.as_array().unwrap().len(), 1);
+    assert_eq!(schedules[0]["id"].to_string(), cron_id);
+
+    // Ids keep growing after a restart
+    let response = register
+        .request(ActionType::Schedule, "test:order", &[("delay", "60000")], b"")
+        .await;
+    let id: u64 = std::str::from_utf8(response.body()).unwrap().parse().unwrap();
+    assert!(id > cron_id.parse().unwrap());
+}
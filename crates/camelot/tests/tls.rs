@@ -0,0 +1,145 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use rcgen::{
+    BasicConstraints, CertificateParams, DnType, ExtendedKeyUsagePurpose, IsCa, KeyPair,
+};
+use server::TestServer;
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use trtcp::StatusType;
+
+/// CA, broker and client certificates generated for a single test
+struct Pki {
+    dir: TempDir,
+}
+
+impl Pki {
+    fn generate(client_cn: &str) -> Self {
+        let dir = tempfile::tempdir().expect("Could not create the temp dir");
+
+        let ca_key = KeyPair::generate().unwrap();
+        let mut ca_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        ca_params.distinguished_name.push(DnType::CommonName, "camelot test CA");
+        let ca = ca_params.self_signed(&ca_key).unwrap();
+
+        let server_key = KeyPair::generate().unwrap();
+        let mut server_params = CertificateParams::new(vec!["localhost".to_string()]).unwrap();
+        server_params.distinguished_name.push(DnType::CommonName, "localhost");
+        server_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+        let server = server_params.signed_by(&server_key, &ca, &ca_key).unwrap();
+
+        let client_key = KeyPair::generate().unwrap();
+        let mut client_params = CertificateParams::new(Vec::<String>::new()).unwrap();
+        client_params.distinguished_name.push(DnType::CommonName, client_cn);
+        client_params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+        let client = client_params.signed_by(&client_key, &ca, &ca_key).unwrap();
+
+        let files = [
+            ("ca.pem", ca.pem()),
+            ("server.pem", server.pem()),
+            ("server.key", server_key.serialize_pem()),
+            ("client.pem", client.pem()),
+            ("client.key", client_key.serialize_pem()),
+        ];
+        for (name, content) in files {
+            std::fs::write(dir.path().join(name), content).unwrap();
+        }
+
+        Self { dir }
+    }
+
+    fn path(&self, name: &str) -> PathBuf {
+        self.dir.path().join(name)
+    }
+
+    fn arg(&self, name: &str) -> String {
+        self.path(name).to_str().unwrap().to_string()
+    }
+
+    async fn start_server(&self, port: u16, mutual: Option<&str>) -> TestServer {
+        let mut args = vec![
+            "--tls-cert".to_string(),
+            self.arg("server.pem"),
+            "--tls-key".to_string(),
+            self.arg("server.key"),
+        ];
+        if let Some(cn_mode) = mutual {
+            args.extend([
+                "--tls-client-ca".to_string(),
+                self.arg("ca.pem"),
+                "--tls-cn".to_string(),
+                cn_mode.to_string(),
+            ]);
+        }
+
+        let args: Vec<&str> = args.iter().map(String::as_str).collect();
+        TestServer::start(port, &args).await
+    }
+
+    async fn client(&self, port: u16, name: &str, with_cert: bool) -> Client {
+        let identity = with_cert.then(|| (self.path("client.pem"), self.path("client.key")));
+        let config = camelot::tls::client_config(
+            &self.path("ca.pem"),
+            identity.as_ref().map(|(c, k)| (Path::new(c), Path::new(k))),
+        )
+        .expect("Could not build the client config");
+
+        Client::connect_tls(&format!("localhost:{}", port), name, config).await
+    }
+}
+
+#[tokio::test]
+async fn tls_call_events() {
+    let pki = Pki::generate("terminal1");
+    let server = pki.start_server(1240, None).await;
+
+    let mut client1 = pki.client(1240, "client1", false).await;
+    let mut client2 = pki.client(1240, "client2", false).await;
+
+    assert_eq!(*client1.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*client2.establish_connection().await.status().r#type(), StatusType::OK);
+
+    assert_eq!(*client1.create_event("tls").await.status().r#type(), StatusType::OK);
+    assert_eq!(*client2.listen_event("tls").await.status().r#type(), StatusType::OK);
+
+    client1.invoke_event("tls", "Hello".as_bytes()).await;
+
+    assert_eq!(*client2.read_request().await.body(), "Hello".as_bytes());
+    assert_eq!(*client1.read_response().await.status().r#type(), StatusType::OK);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn mutual_tls_cn_sets_caller() {
+    let pki = Pki::generate("terminal1");
+    let server = pki.start_server(1241, Some("set")).await;
+
+    let mut client = pki.client(1241, "anything", true).await;
+    let response = client.establish_connection().await;
+
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    assert_eq!(response.head().caller(), "terminal1");
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn mutual_tls_cn_checks_caller() {
+    let pki = Pki::generate("terminal1");
+    let server = pki.start_server(1242, Some("check")).await;
+
+    let mut impostor = pki.client(1242, "terminal2", true).await;
+    let response = impostor.establish_connection().await;
+    assert_eq!(*response.status().r#type(), StatusType::Unauthorized);
+
+    let mut client = pki.client(1242, "terminal1", true).await;
+    let response = client.establish_connection().await;
+    assert_eq!(*response.status().r#type(), StatusType::OK);
+    assert_eq!(response.head().caller(), "terminal1");
+
+    server.stop().await;
+}
@@ -0,0 +1,60 @@
+#![cfg(unix)]
+
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use server::TestServer;
+use std::os::unix::fs::PermissionsExt;
+use trtcp::StatusType;
+
+#[tokio::test]
+async fn unix_socket_only() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("camelot.sock");
+    let server = TestServer::start_unix(&path, &["--unix-mode", "600"]).await;
+
+    let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+    // The socket is bound in a private directory that is gone once it's in place
+    assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 1);
+
+    let mut client1 = Client::connect_unix(&path, "client1").await;
+    let mut client2 = Client::connect_unix(&path, "client2").await;
+
+    assert_eq!(*client1.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*client2.establish_connection().await.status().r#type(), StatusType::OK);
+
+    assert_eq!(*client1.create_event("unix").await.status().r#type(), StatusType::OK);
+    assert_eq!(*client2.listen_event("unix").await.status().r#type(), StatusType::OK);
+
+    client1.invoke_event("unix", "Hello".as_bytes()).await;
+
+    assert_eq!(*client2.read_request().await.body(), "Hello".as_bytes());
+    assert_eq!(*client1.read_response().await.status().r#type(), StatusType::OK);
+
+    server.stop().await;
+}
+
+#[tokio::test]
+async fn unix_socket_alongside_tcp() {
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("camelot.sock");
+    let server = TestServer::start(1243, &["--unix", path.to_str().unwrap()]).await;
+
+    let mut tcp_client = Client::connect("localhost:1243", "tcp").await;
+    let mut unix_client = Client::connect_unix(&path, "unix").await;
+
+    assert_eq!(*tcp_client.establish_connection().await.status().r#type(), StatusType::OK);
+    assert_eq!(*unix_client.establish_connection().await.status().r#type(), StatusType::OK);
+
+    assert_eq!(*tcp_client.create_event("mixed").await.status().r#type(), StatusType::OK);
+    assert_eq!(*tcp_client.listen_event("mixed").await.status().r#type(), StatusType::OK);
+
+    unix_client.invoke_event("mixed", "Hello".as_bytes()).await;
+
+    assert_eq!(*tcp_client.read_request().await.body(), "Hello".as_bytes());
+    assert_eq!(*unix_client.read_response().await.status().r#type(), StatusType::OK);
+
+    server.stop().await;
+}
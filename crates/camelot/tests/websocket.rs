@@ -0,0 +1,87 @@
+mod client;
+mod server;
+
+use client::TestClient as Client;
+use futures_util::{SinkExt, StreamExt};
+use server::TestServer;
+use std::time::Duration;
+use tokio::net::TcpStream;
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{MaybeTlsStream, WebSocketStream};
+use trtcp::{Action, ActionType, Head, Request, Response, StatusType};
+
+type WsClient = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+async fn connect_ws(url: &str) -> WsClient {
+    for _ in 0..50 {
+        if let Ok((socket, _)) = tokio_tungstenite::connect_async(url).await {
+            return socket;
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+    panic!("Could not connect to {}", url);
+}
+
+async fn send(socket: &mut WsClient, r#type: ActionType, event: &str, body: &str) {
+    let request = Request::new(
+        Head::new_with_version("browser"),
+        Action::new(r#type, "test", event),
+        body.as_bytes(),
+    );
+    let bytes: Vec<u8> = request.into();
+
+    socket.send(Message::Binary(bytes.into())).await.unwrap();
+}
+
+async fn receive(socket: &mut WsClient) -> Vec<u8> {
+    loop {
+        match socket.next().await.expect("Socket closed").unwrap() {
+            Message::Binary(bytes) => return bytes.to_vec(),
+            _ => continue,
+        }
+    }
+}
+
+fn status(frame: &[u8]) -> StatusType {
+    Response::try_from(frame).unwrap().status().r#type().clone()
+}
+
+#[tokio::test]
+async fn websocket_shares_events_with_tcp() {
+    let server = TestServer::start(1244, &["--ws", "127.0.0.1:1245"]).await;
+
+    let mut browser = connect_ws("ws://127.0.0.1:1245").await;
+    let mut plugin = Client::connect("localhost:1244", "plugin").await;
+
+    send(&mut browser, ActionType::Connect, "", "").await;
+    assert_eq!(status(&receive(&mut browser).await), StatusType::OK);
+    assert_eq!(*plugin.establish_connection().await.status().r#type(), StatusType::OK);
+
+    send(&mut browser, ActionType::Create, "display", "").await;
+    assert_eq!(status(&receive(&mut browser).await), StatusType::OK);
+
+    send(&mut browser, ActionType::Listen, "display", "").await;
+    assert_eq!(status(&receive(&mut browser).await), StatusType::OK);
+    assert_eq!(*plugin.listen_event("display").await.status().r#type(), StatusType::OK);
+
+    // Invoked from the TCP side, received by the browser
+    plugin.invoke_event("display", "from tcp".as_bytes()).await;
+    assert_eq!(*plugin.read_request().await.body(), "from tcp".as_bytes());
+    assert_eq!(*plugin.read_response().await.status().r#type(), StatusType::OK);
+
+    let callback = receive(&mut browser).await;
+    let callback = Request::try_from(callback.as_slice()).unwrap();
+    assert_eq!(*callback.action().r#type(), ActionType::Callback);
+    assert_eq!(*callback.body(), "from tcp".as_bytes());
+
+    // Invoked from the browser, received by the TCP client
+    send(&mut browser, ActionType::Invoke, "display", "from ws").await;
+    assert_eq!(*plugin.read_request().await.body(), "from ws".as_bytes());
+
+    // The browser listens too, so its callback arrives before the invoke response
+    let callback = receive(&mut browser).await;
+    assert_eq!(*Request::try_from(callback.as_slice()).unwrap().body(), "from ws".as_bytes());
+    assert_eq!(status(&receive(&mut browser).await), StatusType::OK);
+
+    server.stop().await;
+}
@@ -17,6 +17,8 @@ use getset::Getters;
 use std::str;
 
 const SEPARATOR_BYTE: u8 = 0x1F;
+const HEADER_SEPARATOR_BYTE: u8 = 0x1E;
+const HEADER_VALUE_SEPARATOR_BYTE: u8 = 0x1D;
 
 #[derive(Getters, Debug)]
 pub struct Version {
@@ -32,7 +34,7 @@ impl Version {
     }
 
     pub fn actual() -> Self {
-        Version { major: 1, patch: 0 }
+        Version { major: 1, patch: 1 }
     }
 }
 
@@ -66,17 +68,24 @@ pub struct Head<'r> {
     #[get = "pub"]
     version: Version,
     caller: &'r str,
+    #[get = "pub"]
+    headers: Vec<(&'r str, &'r str)>,
 }
 
 impl Head<'_> {
-    pub fn new(version: Version, caller: &str) -> Head {
-        Head { version, caller }
+    pub fn new(version: Version, caller: &str) -> Head<'_> {
+        Head {
+            version,
+            caller,
+            headers: Vec::new(),
+        }
     }
 
-    pub fn new_with_version(caller: &str) -> Head {
+    pub fn new_with_version(caller: &str) -> Head<'_> {
         Head {
             version: Version::actual(),
             caller,
+            headers: Vec::new(),
         }
     }
 
@@ -85,16 +94,52 @@ impl Head<'_> {
     }
 }
 
+impl<'r> Head<'r> {
+    /// Adds a header. Neither the key nor the value can contain the separator bytes
+    pub fn with_header(mut self, key: &'r str, value: &'r str) -> Head<'r> {
+        self.headers.push((key, value));
+        self
+    }
+
+    /// Value of the first header with the given key
+    pub fn header(&self, key: &str) -> Option<&'r str> {
+        self.headers
+            .iter()
+            .find(|(k, _)| *k == key)
+            .map(|(_, value)| *value)
+    }
+}
+
 impl<'a> TryFrom<&'a [u8]> for Head<'a> {
     type Error = Error;
 
     fn try_from(head: &'a [u8]) -> Result<Self, Self::Error> {
+        if head.len() < size_of::<Version>() {
+            return Err(Error::InvalidHead);
+        }
+
         let (version_bytes, rest) = head.split_at(size_of::<Version>());
         let version = version_bytes.try_into()?;
 
-        let caller = str::from_utf8(rest).map_err(|_| Error::InvalidHead)?;
+        let mut sections = rest.split(|&x| x == HEADER_SEPARATOR_BYTE);
+
+        let caller = str::from_utf8(sections.next().unwrap_or_default())
+            .map_err(|_| Error::InvalidHead)?;
 
-        Ok(Head { version, caller })
+        let headers = sections
+            .map(|header| {
+                let header = str::from_utf8(header).map_err(|_| Error::InvalidHead)?;
+                header
+                    .split_once(HEADER_VALUE_SEPARATOR_BYTE as char)
+                    .ok_or(Error::InvalidHead)
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        Ok(Head {
+            version,
+            caller,
+            headers,
+        })
     }
 }
 
@@ -108,6 +153,13 @@ impl From<Head<'_>> for Vec<u8> {
         let caller_bytes = head.caller.as_bytes();
         result.extend_from_slice(caller_bytes);
 
+        for (key, value) in head.headers {
+            result.push(HEADER_SEPARATOR_BYTE);
+            result.extend_from_slice(key.as_bytes());
+            result.push(HEADER_VALUE_SEPARATOR_BYTE);
+            result.extend_from_slice(value.as_bytes());
+        }
+
         result
     }
 }
@@ -121,6 +173,7 @@ mod test {
         let head = Head {
             version: Version { major: 1, patch: 2 },
             caller: "345",
+            headers: Vec::new(),
         };
 
         let bytes: Vec<u8> = head.into();
@@ -150,11 +203,44 @@ mod test {
         assert_eq!(head.caller, "345");
     }
 
+    #[test]
+    fn test_head_with_headers() {
+        let head = Head::new(Version { major: 1, patch: 1 }, "345")
+            .with_header("durable", "true")
+            .with_header("group", "printers");
+
+        let bytes: Vec<u8> = head.into();
+
+        assert_eq!(
+            bytes,
+            vec![
+                0, 1, // major (1)
+                0, 1, // patch (1)
+                51, 52, 53, // caller ("345")
+                0x1E, // header separator
+                100, 117, 114, 97, 98, 108, 101, // key ("durable")
+                0x1D, // value separator
+                116, 114, 117, 101, // value ("true")
+                0x1E, // header separator
+                103, 114, 111, 117, 112, // key ("group")
+                0x1D, // value separator
+                112, 114, 105, 110, 116, 101, 114, 115, // value ("printers")
+            ]
+        );
+
+        let head: Head = bytes.as_slice().try_into().unwrap();
+
+        assert_eq!(head.caller, "345");
+        assert_eq!(head.header("durable"), Some("true"));
+        assert_eq!(head.header("group"), Some("printers"));
+        assert_eq!(head.header("filter"), None);
+    }
+
     #[test]
     fn test_version_into_bytes() {
         let version = Version { major: 1, patch: 2 };
 
-        let bytes: Vec<u8> = version.try_into().unwrap();
+        let bytes: Vec<u8> = version.into();
 
         assert_eq!(
             bytes,
@@ -21,6 +21,28 @@ impl Request<'_> {
     pub fn body_as_str(&self) -> Result<&str, std::str::Utf8Error> {
         std::str::from_utf8(self.body)
     }
+
+    /// Replaces the caller of the head, keeping the rest of the request untouched
+    pub fn with_caller<'c>(self, caller: &'c str) -> Request<'c>
+    where
+        Self: 'c,
+    {
+        Request {
+            head: Head {
+                version: self.head.version,
+                caller,
+                headers: self.head.headers,
+            },
+            action: self.action,
+            body: self.body,
+        }
+    }
+
+    /// Drops every header with the given key, for the ones only the server can set
+    pub fn without_header(mut self, key: &str) -> Self {
+        self.head.headers.retain(|(k, _)| *k != key);
+        self
+    }
 }
 
 impl<'r> TryFrom<&'r [u8]> for Request<'r> {
@@ -42,7 +64,8 @@ impl<'r> TryFrom<&'r [u8]> for Request<'r> {
             return Err(crate::Error::InvalidRequest);
         }
         
-        let split_request = request.split(|&x| x == SEPARATOR_BYTE).collect::<Vec<&[u8]>>();
+        // The body is the last section, so it can contain separator bytes
+        let split_request = request.splitn(3, |&x| x == SEPARATOR_BYTE).collect::<Vec<&[u8]>>();
 
         if split_request.len() != 3 {
             return Err(crate::Error::InvalidRequest);
@@ -139,6 +162,19 @@ pub enum ActionType {
     Leave,
     Create,
     Callback,
+    Ack,
+    Clear,
+    History,
+    Delete,
+    Request,
+    Reply,
+    Disconnect,
+    Events,
+    Describe,
+    Subscriptions,
+    Schedule,
+    Schedules,
+    Cancel,
 }
 
 impl TryFrom<&[u8]> for ActionType {
@@ -152,6 +188,19 @@ impl TryFrom<&[u8]> for ActionType {
             [3] => Ok(ActionType::Create),
             [4] => Ok(ActionType::Leave),
             [5] => Ok(ActionType::Callback),
+            [6] => Ok(ActionType::Ack),
+            [7] => Ok(ActionType::Clear),
+            [8] => Ok(ActionType::History),
+            [9] => Ok(ActionType::Delete),
+            [10] => Ok(ActionType::Request),
+            [11] => Ok(ActionType::Reply),
+            [12] => Ok(ActionType::Disconnect),
+            [13] => Ok(ActionType::Events),
+            [14] => Ok(ActionType::Describe),
+            [15] => Ok(ActionType::Subscriptions),
+            [16] => Ok(ActionType::Schedule),
+            [17] => Ok(ActionType::Schedules),
+            [18] => Ok(ActionType::Cancel),
             _ => Err(crate::Error::InvalidActionType),
         }
     }
@@ -166,6 +215,75 @@ impl From<ActionType> for Vec<u8> {
             ActionType::Create => vec![3],
             ActionType::Leave => vec![4],
             ActionType::Callback => vec![5],
+            ActionType::Ack => vec![6],
+            ActionType::Clear => vec![7],
+            ActionType::History => vec![8],
+            ActionType::Delete => vec![9],
+            ActionType::Request => vec![10],
+            ActionType::Reply => vec![11],
+            ActionType::Disconnect => vec![12],
+            ActionType::Events => vec![13],
+            ActionType::Describe => vec![14],
+            ActionType::Subscriptions => vec![15],
+            ActionType::Schedule => vec![16],
+            ActionType::Schedules => vec![17],
+            ActionType::Cancel => vec![18],
+        }
+    }
+}
+
+impl ActionType {
+    /// Lowercase name of the action type, used by the text based clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            ActionType::Connect => "connect",
+            ActionType::Listen => "listen",
+            ActionType::Invoke => "invoke",
+            ActionType::Create => "create",
+            ActionType::Leave => "leave",
+            ActionType::Callback => "callback",
+            ActionType::Ack => "ack",
+            ActionType::Clear => "clear",
+            ActionType::History => "history",
+            ActionType::Delete => "delete",
+            ActionType::Request => "request",
+            ActionType::Reply => "reply",
+            ActionType::Disconnect => "disconnect",
+            ActionType::Events => "events",
+            ActionType::Describe => "describe",
+            ActionType::Subscriptions => "subscriptions",
+            ActionType::Schedule => "schedule",
+            ActionType::Schedules => "schedules",
+            ActionType::Cancel => "cancel",
+        }
+    }
+}
+
+impl std::str::FromStr for ActionType {
+    type Err = crate::Error;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "connect" => Ok(ActionType::Connect),
+            "listen" => Ok(ActionType::Listen),
+            "invoke" => Ok(ActionType::Invoke),
+            "create" => Ok(ActionType::Create),
+            "leave" => Ok(ActionType::Leave),
+            "callback" => Ok(ActionType::Callback),
+            "ack" => Ok(ActionType::Ack),
+            "clear" => Ok(ActionType::Clear),
+            "history" => Ok(ActionType::History),
+            "delete" => Ok(ActionType::Delete),
+            "request" => Ok(ActionType::Request),
+            "reply" => Ok(ActionType::Reply),
+            "disconnect" => Ok(ActionType::Disconnect),
+            "events" => Ok(ActionType::Events),
+            "describe" => Ok(ActionType::Describe),
+            "subscriptions" => Ok(ActionType::Subscriptions),
+            "schedule" => Ok(ActionType::Schedule),
+            "schedules" => Ok(ActionType::Schedules),
+            "cancel" => Ok(ActionType::Cancel),
+            _ => Err(crate::Error::InvalidActionType),
         }
     }
 }
@@ -180,6 +298,7 @@ mod test {
             head: Head {
                 version: crate::Version { major: 1, patch: 2 },
                 caller: "345",
+                headers: Vec::new(),
             },
             action: Action {
                 r#type: ActionType::Listen,
@@ -206,6 +325,37 @@ mod test {
         assert_eq!(request.body, "hello".as_bytes());
     }
 
+    #[test]
+    fn test_request_without_header() {
+        let request = Request::new(
+            Head::new_with_version("345")
+                .with_header("schedule-id", "7")
+                .with_header("durable", "true"),
+            Action::new(ActionType::Invoke, "ns", "id"),
+            "".as_bytes(),
+        )
+        .without_header("schedule-id");
+
+        assert_eq!(request.head().header("schedule-id"), None);
+        assert_eq!(request.head().header("durable"), Some("true"));
+    }
+
+    #[test]
+    fn test_request_with_separator_in_body() {
+        let request = Request::new(
+            Head::new_with_version("345").with_header("durable", "true"),
+            Action::new(ActionType::Invoke, "ns", "id"),
+            [104, 0x1F, 105].as_slice(),
+        );
+
+        let bytes: Vec<u8> = request.into();
+        let request = Request::try_from(&bytes[..]).unwrap();
+
+        assert_eq!(request.head.caller, "345");
+        assert_eq!(request.head.header("durable"), Some("true"));
+        assert_eq!(request.body, [104, 0x1F, 105]);
+    }
+
     #[test]
     fn test_bytes_into_request() {
         let request: &[u8] = &[
@@ -242,6 +392,7 @@ mod test {
             head: Head {
                 version: crate::Version { major: 1, patch: 2 },
                 caller: "345",
+                headers: Vec::new(),
             },
             action: Action {
                 r#type: ActionType::Leave,
@@ -284,6 +435,35 @@ mod test {
         assert_eq!(action.id, "id");
     }
 
+    #[test]
+    fn test_action_type_names() {
+        for r#type in [
+            ActionType::Connect,
+            ActionType::Listen,
+            ActionType::Invoke,
+            ActionType::Create,
+            ActionType::Leave,
+            ActionType::Callback,
+            ActionType::Ack,
+            ActionType::Clear,
+            ActionType::History,
+            ActionType::Delete,
+            ActionType::Request,
+            ActionType::Reply,
+            ActionType::Disconnect,
+            ActionType::Events,
+            ActionType::Describe,
+            ActionType::Subscriptions,
+            ActionType::Schedule,
+            ActionType::Schedules,
+            ActionType::Cancel,
+        ] {
+            assert_eq!(r#type.name().parse::<ActionType>().unwrap(), r#type);
+        }
+
+        assert!("unknown".parse::<ActionType>().is_err());
+    }
+
     #[test]
     fn test_action_into_bytes() {
         let action = Action {
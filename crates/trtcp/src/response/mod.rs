@@ -1,5 +1,6 @@
 use crate::{Head, SEPARATOR_BYTE};
 use getset::Getters;
+use std::borrow::Cow;
 
 const START_BYTE: u8 = 0x01;
 
@@ -9,8 +10,7 @@ pub struct Response<'r> {
     head: Head<'r>,
     #[get = "pub"]
     status: Status,
-    #[get = "pub"]
-    body: &'r [u8],
+    body: Cow<'r, [u8]>,
 }
 
 impl Response<'_> {
@@ -18,19 +18,32 @@ impl Response<'_> {
         Response {
             head,
             status,
-            body: body.into(),
+            body: Cow::Borrowed(body.into()),
+        }
+    }
+
+    /// Builds a response whose body is generated by the handler instead of borrowed
+    pub fn new_owned(head: Head<'_>, status: Status, body: Vec<u8>) -> Response<'_> {
+        Response {
+            head,
+            status,
+            body: Cow::Owned(body),
         }
     }
 
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
     pub fn body_as_str(&self) -> Result<&str, std::str::Utf8Error> {
-        std::str::from_utf8(self.body)
+        std::str::from_utf8(&self.body)
     }
     
-    pub fn new_ok(caller: &str) -> Response {
+    pub fn new_ok(caller: &str) -> Response<'_> {
         Response {
             head: Head::new_with_version(caller),
             status: Status::new(StatusType::OK),
-            body: "".as_bytes(),
+            body: Cow::Borrowed("".as_bytes()),
         }
     }
     
@@ -38,7 +51,7 @@ impl Response<'_> {
         Response {
             head: Head::new_with_version(caller),
             status: Status::new(StatusType::GenericError),
-            body: error_msg.as_bytes(),
+            body: Cow::Borrowed(error_msg.as_bytes()),
         }
     }
 }
@@ -63,7 +76,8 @@ impl<'r> TryFrom<&'r [u8]> for Response<'r> {
             return Err(crate::Error::InvalidResponse);
         }
         
-        let split_response = response.split(|&x| x == SEPARATOR_BYTE).collect::<Vec<&[u8]>>();
+        // The body is the last section, so it can contain separator bytes
+        let split_response = response.splitn(3, |&x| x == SEPARATOR_BYTE).collect::<Vec<&[u8]>>();
 
         if split_response.len() != 3 {
             return Err(crate::Error::InvalidResponse);
@@ -71,7 +85,7 @@ impl<'r> TryFrom<&'r [u8]> for Response<'r> {
 
         let head = split_response[0].try_into()?;
         let status = split_response[1].try_into()?;
-        let body = split_response[2];
+        let body = Cow::Borrowed(split_response[2]);
 
         Ok(Response { head, status, body })
     }
@@ -91,7 +105,7 @@ impl<'r> From<Response<'r>> for Vec<u8> {
 
         result.push(0x1F);
 
-        result.extend_from_slice(response.body);
+        result.extend_from_slice(&response.body);
         
         let length = (result.len() as u32).to_be_bytes();
         let msg_type = START_BYTE.to_be_bytes();
@@ -155,6 +169,7 @@ pub enum StatusType {
     GenericError,        // -1
     NeedConnection,      // -2
     InternalServerError, // -3
+    Unauthorized,        // -4
     // Warnings
     AlreadyConnected,   // 1
     InvalidRequest,     // 2
@@ -162,6 +177,10 @@ pub enum StatusType {
     ListenerNotFound,   // 4
     EventAlreadyExists, // 5
     AlreadySubscribed, // 6
+    Forbidden, // 7
+    Timeout, // 8
+    InvalidPayload, // 9
+    ScheduleNotFound, // 10
 }
 
 impl TryFrom<i8> for StatusType {
@@ -173,12 +192,17 @@ impl TryFrom<i8> for StatusType {
             -1 => Ok(StatusType::GenericError),
             -2 => Ok(StatusType::NeedConnection),
             -3 => Ok(StatusType::InternalServerError),
+            -4 => Ok(StatusType::Unauthorized),
             1 => Ok(StatusType::AlreadyConnected),
             2 => Ok(StatusType::InvalidRequest),
             3 => Ok(StatusType::EventNotFound),
             4 => Ok(StatusType::ListenerNotFound),
             5 => Ok(StatusType::EventAlreadyExists),
             6 => Ok(StatusType::AlreadySubscribed),
+            7 => Ok(StatusType::Forbidden),
+            8 => Ok(StatusType::Timeout),
+            9 => Ok(StatusType::InvalidPayload),
+            10 => Ok(StatusType::ScheduleNotFound),
             _ => Err(crate::Error::InvalidStatus),
         }
     }
@@ -191,12 +215,40 @@ impl From<StatusType> for i8 {
             StatusType::GenericError => -1,
             StatusType::NeedConnection => -2,
             StatusType::InternalServerError => -3,
+            StatusType::Unauthorized => -4,
             StatusType::AlreadyConnected => 1,
             StatusType::InvalidRequest => 2,
             StatusType::EventNotFound => 3,
             StatusType::ListenerNotFound => 4,
             StatusType::EventAlreadyExists => 5,
             StatusType::AlreadySubscribed => 6,
+            StatusType::Forbidden => 7,
+            StatusType::Timeout => 8,
+            StatusType::InvalidPayload => 9,
+            StatusType::ScheduleNotFound => 10,
+        }
+    }
+}
+
+impl StatusType {
+    /// Name of the status, used by the text based clients
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusType::OK => "OK",
+            StatusType::GenericError => "GenericError",
+            StatusType::NeedConnection => "NeedConnection",
+            StatusType::InternalServerError => "InternalServerError",
+            StatusType::Unauthorized => "Unauthorized",
+            StatusType::AlreadyConnected => "AlreadyConnected",
+            StatusType::InvalidRequest => "InvalidRequest",
+            StatusType::EventNotFound => "EventNotFound",
+            StatusType::ListenerNotFound => "ListenerNotFound",
+            StatusType::EventAlreadyExists => "EventAlreadyExists",
+            StatusType::AlreadySubscribed => "AlreadySubscribed",
+            StatusType::Forbidden => "Forbidden",
+            StatusType::Timeout => "Timeout",
+            StatusType::InvalidPayload => "InvalidPayload",
+            StatusType::ScheduleNotFound => "ScheduleNotFound",
         }
     }
 }
@@ -212,11 +264,12 @@ mod test {
             head: Head {
                 version: Version { major: 1, patch: 2 },
                 caller: "345",
+                headers: Vec::new(),
             },
             status: Status {
                 r#type: StatusType::GenericError,
             },
-            body: "345".as_bytes(),
+            body: "345".as_bytes().into(),
         };
 
         let bytes: Vec<u8> = response.into();
@@ -259,11 +312,12 @@ mod test {
             head: Head {
                 version: Version { major: 1, patch: 2 },
                 caller: "345",
+                headers: Vec::new(),
             },
             status: Status {
                 r#type: StatusType::OK,
             },
-            body: "345".as_bytes(),
+            body: "345".as_bytes().into(),
         };
 
         let bytes: Vec<u8> = response.into();